@@ -0,0 +1,403 @@
+use std::collections::BTreeSet;
+use chem::Id;
+use creature::{Locus, LocusId};
+use dsl::Names;
+use genome::{Gene, Genome};
+use graph::{Network, Node};
+use life::STAGE_LOCI;
+use organ::Direction;
+
+/// What can be said about a genome's biochemistry without running it.
+#[derive(Clone, Debug)]
+pub struct Analysis {
+    /// Chemicals that can ever be present, starting from the initial state. Emitters count as
+    /// sources, since whether they fire depends on loci rather than chemistry.
+    pub reachable: BTreeSet<Id>,
+    /// Chemicals some gene refers to that can never be present.
+    pub unreachable: BTreeSet<Id>,
+    /// Reachable chemicals that no reaction consumes and no receptor senses.
+    pub dead_ends: BTreeSet<Id>,
+    /// Chemicals that cannot affect any locus, directly or through reactions.
+    pub unused: BTreeSet<Id>,
+    /// Loci that emitters read but no receptor writes.
+    pub unwritten_loci: BTreeSet<LocusId>,
+    /// Reactions, by gene index, that can never fire because a reactant is unreachable.
+    pub inert_reactions: Vec<usize>,
+    /// Feedback loops in the reaction network, each a strongly connected set of nodes.
+    pub cycles: Vec<Vec<Node>>,
+    /// A basis of the weighted sums of concentrations that no reaction or emitter changes, as
+    /// (chemical, weight) pairs.
+    pub conserved: Vec<Vec<(Id, i64)>>,
+    /// Genes, by index, that have no effect on any locus and could be removed.
+    pub junk: Vec<usize>,
+}
+
+impl Genome {
+    pub fn analyze(&self) -> Analysis {
+        let genes = self.genes();
+        let mut mentioned = BTreeSet::new();
+        let mut reachable = BTreeSet::new();
+        let mut read = BTreeSet::new();
+        let mut sensed = BTreeSet::new();
+        let mut imported = BTreeSet::new();
+        // The life clock keeps the stage loci up to date whether or not there is a clock gene, and
+        // every creature has its sex written.
+        let mut written: BTreeSet<LocusId> = STAGE_LOCI.iter().cloned().chain(Some(Locus::Sex))
+            .map(|l| l as LocusId).collect();
+        let mut loci = BTreeSet::new();
+        for gene in genes.iter() {
+            match *gene.unlinked() {
+                Gene::InitialState(ref c) => {
+                    mentioned.insert(c.id());
+                    if c.concnt() > 0 { reachable.insert(c.id()); }
+                },
+                Gene::Emitter(ref e) => {
+                    mentioned.insert(e.chemical);
+                    reachable.insert(e.chemical);
+                    loci.insert(e.locus);
+                },
+                Gene::Reaction(ref r) => {
+                    mentioned.extend(r.kind.reagents().iter().map(|c| c.id));
+                    read.extend(r.kind.reactants().iter().map(|c| c.id));
+                },
+                Gene::Receptor(ref r) => {
+                    mentioned.insert(r.chemical);
+                    sensed.insert(r.chemical);
+                    written.insert(r.locus);
+                },
+                // Organs have compartments of their own and are left out, apart from what they
+                // take from and give to the bloodstream.
+                Gene::Organ(ref o) => for t in o.transport.iter() {
+                    mentioned.insert(t.chemical);
+                    match t.direction {
+                        Direction::Import => { imported.insert(t.chemical); },
+                        Direction::Export => { reachable.insert(t.chemical); },
+                    }
+                },
+                Gene::Brain(..) | Gene::Clock(..) | Gene::SexLinked(..) => (),
+            }
+        }
+        read.extend(sensed.iter().cloned());
+        read.extend(imported.iter().cloned());
+
+        // A reaction fires once all of its reactants can be present, making its products
+        // available in turn, so keep firing reactions until nothing new appears.
+        let reactions: Vec<(usize, Vec<Id>, Vec<Id>)> = genes.iter().enumerate()
+            .filter_map(|(n, gene)| match *gene.unlinked() {
+                Gene::Reaction(ref r) => Some((
+                    n,
+                    r.kind.reactants().iter().map(|c| c.id).collect(),
+                    r.kind.products().iter().map(|c| c.id).collect(),
+                )),
+                _ => None,
+            }).collect();
+        let mut fired = vec![false; reactions.len()];
+        loop {
+            let mut changed = false;
+            for (i, &(_, ref reactants, ref products)) in reactions.iter().enumerate() {
+                if !fired[i] && reactants.iter().all(|c| reachable.contains(c)) {
+                    fired[i] = true;
+                    changed = true;
+                    reachable.extend(products.iter().cloned());
+                }
+            }
+            if !changed { break }
+        }
+
+        // Work backwards from receptors and organs: a reaction matters if any chemical it touches
+        // does, and then everything it consumes matters too, since consuming it changes what is
+        // left.
+        let mut useful: BTreeSet<Id> = sensed.union(&imported).cloned().collect();
+        loop {
+            let mut changed = false;
+            for &(_, ref reactants, ref products) in reactions.iter() {
+                let matters = reactants.iter().chain(products.iter()).any(|c| useful.contains(c));
+                if matters {
+                    for &c in reactants.iter() {
+                        changed |= useful.insert(c);
+                    }
+                }
+            }
+            if !changed { break }
+        }
+
+        let inert_reactions: Vec<usize> = reactions.iter().zip(fired.iter())
+            .filter(|&(_, &fired)| !fired).map(|(r, _)| r.0).collect();
+        let junk = genes.iter().enumerate().filter(|&(n, gene)| match *gene.unlinked() {
+            Gene::InitialState(ref c) => !useful.contains(&c.id()),
+            Gene::Emitter(ref e) => !useful.contains(&e.chemical),
+            Gene::Reaction(ref r) => {
+                inert_reactions.contains(&n) || r.kind.reagents().iter().all(|c| {
+                    !useful.contains(&c.id)
+                })
+            },
+            _ => false,
+        }).map(|(n, _)| n).collect();
+
+        Analysis {
+            unreachable: mentioned.difference(&reachable).cloned().collect(),
+            dead_ends: reachable.difference(&read).cloned().collect(),
+            unused: mentioned.difference(&useful).cloned().collect(),
+            unwritten_loci: loci.difference(&written).cloned().collect(),
+            inert_reactions: inert_reactions,
+            cycles: cycles(&Network::from_genome(self)),
+            conserved: conserved(genes),
+            junk: junk,
+            reachable: reachable,
+        }
+    }
+}
+
+impl Analysis {
+    /// A plain text report, naming chemicals and loci with `names`.
+    pub fn report(&self, names: &Names) -> String {
+        let chemicals = |set: &BTreeSet<Id>| -> String {
+            set.iter().map(|&id| names.chemical(id)).collect::<Vec<_>>().join(", ")
+        };
+        let node = |node: &Node| match *node {
+            Node::Chemical(id) => names.chemical(id),
+            Node::Locus(id) => format!("locus {}", names.locus(id)),
+            Node::Reaction(n) => format!("reaction #{}", n),
+        };
+        let mut out = String::new();
+        out.push_str(&format!("reachable: {}\n", chemicals(&self.reachable)));
+        out.push_str(&format!("unreachable: {}\n", chemicals(&self.unreachable)));
+        out.push_str(&format!("dead ends: {}\n", chemicals(&self.dead_ends)));
+        out.push_str(&format!("unused: {}\n", chemicals(&self.unused)));
+        out.push_str(&format!("unwritten loci: {}\n", self.unwritten_loci.iter().map(|&id| {
+            names.locus(id)
+        }).collect::<Vec<_>>().join(", ")));
+        out.push_str(&format!("inert reactions: {}\n", self.inert_reactions.iter().map(|n| {
+            format!("#{}", n)
+        }).collect::<Vec<_>>().join(", ")));
+        out.push_str("cycles:\n");
+        for cycle in self.cycles.iter() {
+            out.push_str(&format!("    {}\n", cycle.iter().map(&node).collect::<Vec<_>>()
+                                                     .join(", ")));
+        }
+        out.push_str("conserved:\n");
+        for sum in self.conserved.iter() {
+            out.push_str(&format!("    {}\n", sum.iter().map(|&(id, weight)| if weight == 1 {
+                names.chemical(id)
+            } else {
+                format!("{}*{}", weight, names.chemical(id))
+            }).collect::<Vec<_>>().join(" + ")));
+        }
+        out.push_str(&format!("junk genes: {}\n", self.junk.iter().map(|n| {
+            format!("#{}", n)
+        }).collect::<Vec<_>>().join(", ")));
+        out
+    }
+}
+
+/// Finds the strongly connected components of the network with more than one node, using
+/// Tarjan's algorithm.
+fn cycles(network: &Network) -> Vec<Vec<Node>> {
+    struct State {
+        next: usize,
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        components: Vec<Vec<usize>>,
+    }
+
+    fn visit(v: usize, adjacent: &[Vec<usize>], state: &mut State) {
+        state.index[v] = Some(state.next);
+        state.low[v] = state.next;
+        state.next += 1;
+        state.stack.push(v);
+        state.on_stack[v] = true;
+        for &w in adjacent[v].iter() {
+            match state.index[w] {
+                None => {
+                    visit(w, adjacent, state);
+                    state.low[v] = ::std::cmp::min(state.low[v], state.low[w]);
+                },
+                Some(index) => if state.on_stack[w] {
+                    state.low[v] = ::std::cmp::min(state.low[v], index);
+                },
+            }
+        }
+        if Some(state.low[v]) == state.index[v] {
+            let mut component = Vec::new();
+            while let Some(w) = state.stack.pop() {
+                state.on_stack[w] = false;
+                component.push(w);
+                if w == v { break }
+            }
+            if component.len() > 1 {
+                component.reverse();
+                state.components.push(component);
+            }
+        }
+    }
+
+    let position = |node: Node| network.nodes.iter().position(|&n| n == node).unwrap();
+    let mut adjacent = vec![Vec::new(); network.nodes.len()];
+    for edge in network.edges.iter() {
+        adjacent[position(edge.from)].push(position(edge.to));
+    }
+    let len = network.nodes.len();
+    let mut state = State {
+        next: 0, index: vec![None; len], low: vec![0; len], stack: Vec::new(),
+        on_stack: vec![false; len], components: Vec::new()
+    };
+    for v in 0 .. len {
+        if state.index[v].is_none() {
+            visit(v, &adjacent, &mut state);
+        }
+    }
+    state.components.into_iter().map(|component| {
+        component.into_iter().map(|v| network.nodes[v]).collect()
+    }).collect()
+}
+
+/// Finds a basis of the left null space of the stoichiometry matrix, whose rows are the chemicals
+/// taking part in reactions and whose columns are the net change made by each reaction or emitter.
+fn conserved(genes: &[Gene]) -> Vec<Vec<(Id, i64)>> {
+    let chemicals: Vec<Id> = genes.iter().filter_map(|gene| match *gene.unlinked() {
+        Gene::Reaction(ref r) => Some(r.kind.reagents()),
+        _ => None,
+    }).flat_map(|reagents| reagents.into_iter().map(|c| c.id))
+      .collect::<BTreeSet<_>>().into_iter().collect();
+    if chemicals.is_empty() { return Vec::new() }
+    let column = |id: Id| chemicals.iter().position(|&c| c == id);
+
+    // Each row here is one reaction's net change, so the null space of this matrix is the left
+    // null space of the stoichiometry matrix.
+    let mut rows: Vec<Vec<i64>> = Vec::new();
+    for gene in genes.iter() {
+        let mut row = vec![0; chemicals.len()];
+        match *gene.unlinked() {
+            Gene::Reaction(ref r) => {
+                for c in r.kind.reactants() {
+                    row[column(c.id).unwrap()] -= c.coefficient.get() as i64;
+                }
+                for c in r.kind.products() {
+                    row[column(c.id).unwrap()] += c.coefficient.get() as i64;
+                }
+            },
+            Gene::Emitter(ref e) => match column(e.chemical) {
+                Some(i) => row[i] = 1,
+                None => continue,
+            },
+            _ => continue,
+        }
+        if row.iter().any(|&x| x != 0) {
+            rows.push(row);
+        }
+    }
+
+    // Reduce to row echelon form with every pivot column cleared above and below, staying in
+    // integers so that the weights come out exact.
+    let mut pivots = Vec::new();
+    for col in 0 .. chemicals.len() {
+        let r = pivots.len();
+        let pivot = match (r .. rows.len()).find(|&i| rows[i][col] != 0) {
+            Some(i) => i,
+            None => continue,
+        };
+        rows.swap(r, pivot);
+        for i in 0 .. rows.len() {
+            if i == r || rows[i][col] == 0 { continue }
+            let (p, q) = (rows[r][col], rows[i][col]);
+            let reduced: Vec<i64> = rows[i].iter().zip(rows[r].iter()).map(|(&x, &y)| {
+                x * p - y * q
+            }).collect();
+            rows[i] = normalise(reduced);
+        }
+        pivots.push(col);
+    }
+
+    let scale = pivots.iter().enumerate().fold(1, |l, (r, &col)| lcm(l, rows[r][col].abs()));
+    (0 .. chemicals.len()).filter(|col| !pivots.contains(col)).map(|free| {
+        let mut weights = vec![0; chemicals.len()];
+        weights[free] = scale;
+        for (r, &col) in pivots.iter().enumerate() {
+            weights[col] = -rows[r][free] * scale / rows[r][col];
+        }
+        let mut weights = normalise(weights);
+        if weights.iter().find(|&&x| x != 0).map_or(false, |&x| x < 0) {
+            for x in weights.iter_mut() { *x = -*x; }
+        }
+        chemicals.iter().cloned().zip(weights).filter(|&(_, w)| w != 0).collect()
+    }).collect()
+}
+
+fn normalise(row: Vec<i64>) -> Vec<i64> {
+    let divisor = row.iter().fold(0, |d, &x| gcd(d, x.abs()));
+    if divisor <= 1 { return row }
+    row.into_iter().map(|x| x / divisor).collect()
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+fn lcm(a: i64, b: i64) -> i64 {
+    a / gcd(a, b) * b
+}
+
+#[cfg(test)]
+mod tests {
+    use dsl::parse_genome;
+    use super::*;
+
+    fn analyze(text: &str) -> Analysis {
+        parse_genome(text).unwrap().0.analyze()
+    }
+
+    fn set<T: Ord + Copy>(items: &[T]) -> BTreeSet<T> {
+        items.iter().cloned().collect()
+    }
+
+    #[test]
+    fn reactions_missing_a_reactant_never_fire() {
+        let analysis = analyze("initial 1 10
+reaction normal 1*1 + 1*2 -> 1*3 + 1*4
+reaction decay 1*1 -> nothing
+receptor digital chem 1 > 0 -> locus hunger nominal 0 gain 255");
+        assert_eq!(analysis.reachable, set(&[1]));
+        assert_eq!(analysis.unreachable, set(&[2, 3, 4]));
+        assert_eq!(analysis.inert_reactions, vec![1]);
+        assert_eq!(analysis.junk, vec![1]);
+        assert!(analysis.dead_ends.is_empty());
+    }
+
+    #[test]
+    fn chemicals_that_reach_no_locus_are_unused() {
+        let analysis = analyze("initial 1 10
+initial 2 10
+reaction fusion 1*1 + 1*2 -> 1*3
+receptor digital chem 1 > 0 -> locus hunger nominal 0 gain 255");
+        assert_eq!(analysis.reachable, set(&[1, 2, 3]));
+        assert_eq!(analysis.unused, set(&[3]));
+        assert_eq!(analysis.dead_ends, set(&[3]));
+        assert!(analysis.junk.is_empty());
+        assert_eq!(analysis.conserved.len(), 2);
+    }
+
+    #[test]
+    fn only_loci_nothing_writes_are_unwritten() {
+        let analysis = analyze("emitter digital locus 40 >= 1 -> +1 chem 1
+emitter digital locus sex >= 1 -> +1 chem 1
+emitter digital locus aged_to_adult >= 1 -> +1 chem 1
+emitter digital locus 41 >= 1 -> +1 chem 1
+receptor digital chem 1 > 0 -> locus 41 nominal 0 gain 255");
+        assert_eq!(analysis.unwritten_loci, set(&[40]));
+    }
+
+    #[test]
+    fn organs_take_and_give_chemicals() {
+        let analysis = analyze("initial 1 10
+organ toxin 9 repair 9 import 1 5 export 2 5
+    reaction decay 1*9 -> nothing
+end
+reaction decay 1*2 -> nothing");
+        assert!(analysis.reachable.contains(&2));
+        assert!(analysis.unreachable.is_empty());
+        assert!(!analysis.unused.contains(&1));
+        assert!(analysis.inert_reactions.is_empty());
+    }
+}
@@ -1,17 +1,109 @@
 extern crate backend;
 
+use std::env::args;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::prelude::*;
 use backend::*;
 
+/// How many ticks before a death to report on.
+const HISTORY: usize = 20;
+
+fn usage(program: &str) {
+    println!(concat!("Usage: {} [genome] [--record <file>] [--plot <file.svg>] [--every <ticks>] ",
+                     "[--only <channel,...>] [--double-buffered]"), program);
+    println!("Recordings ending in .csv are written as CSV, others in binary. Channels are named");
+    println!("like chem:glucose or locus:hunger.");
+    println!("With --double-buffered, genes all act on the state from the previous tick.");
+}
+
+fn channel(names: &Names, label: &str) -> Option<Channel> {
+    if label.starts_with("chem:") {
+        names.lookup_chemical(&label[5..]).map(Channel::Chemical)
+    } else if label.starts_with("locus:") {
+        names.lookup_locus(&label[6..]).map(Channel::Locus)
+    } else {
+        None
+    }
+}
+
 fn main() {
-    let genome = Genome::load("evolved.json").unwrap();
+    let args: Vec<String> = args().collect();
+    let (mut path, mut every) = ("evolved.json", 1);
+    let (mut record, mut plot, mut only) = (None, None, None);
+    let mut mode = TickMode::Sequential;
+    let mut n = 1;
+    while n < args.len() {
+        match (&args[n][..], args.get(n + 1)) {
+            ("--double-buffered", _) => {
+                mode = TickMode::DoubleBuffered;
+                n += 1;
+                continue
+            },
+            ("--record", Some(file)) => record = Some(file),
+            ("--plot", Some(file)) => plot = Some(file),
+            ("--every", Some(ticks)) => match ticks.parse() {
+                Ok(ticks) if ticks > 0 => every = ticks,
+                _ => return usage(&args[0]),
+            },
+            ("--only", Some(channels)) => only = Some(channels),
+            (arg, _) if !arg.starts_with("--") => {
+                path = arg;
+                n += 1;
+                continue
+            },
+            _ => return usage(&args[0]),
+        }
+        n += 2;
+    }
+
+    let (genome, names) = load_genome(path).unwrap();
+    let mut recorder = match only {
+        Some(labels) => {
+            let channels: Option<Vec<Channel>> = labels.split(',').map(|label| {
+                channel(&names, label)
+            }).collect();
+            match channels {
+                Some(channels) => Recorder::with_channels(channels, every),
+                None => return usage(&args[0]),
+            }
+        },
+        None => Recorder::new(every),
+    };
+
     let mut creature = Creature::new();
     genome.init(&mut creature);
-    let mut age = creature.age();
-    for n in 0 .. 600 {
-        genome.step(&mut creature);
-        if creature.age() != age {
-            println!("Creature aged from {:?} to {:?} at t = {}.", age, creature.age(), n);
-            age = creature.age();
+    recorder.observe(&creature);
+    let mut history = History::new(HISTORY);
+    let mut stages = Vec::new();
+    for _ in 0 .. 600 {
+        genome.tick(&mut creature, mode);
+        recorder.observe(&creature);
+        history.observe(&creature);
+        for (tick, event) in creature.drain_events() {
+            match event {
+                Event::StageChanged { from, to } => {
+                    println!("Creature aged from {:?} to {:?} at t = {}.", from, to, tick);
+                    stages.push((tick, to));
+                },
+                Event::Died(_) => {
+                    print!("Creature {}", PostMortem::new(&creature, &history).report(&names));
+                },
+                _ => (),
+            }
         }
     }
+
+    if let Some(file) = record {
+        let mut out = BufWriter::new(File::create(file).unwrap());
+        if file.ends_with(".csv") {
+            recorder.write_csv(&mut out, &names).unwrap();
+        } else {
+            recorder.write_binary(&mut out).unwrap();
+        }
+    }
+    if let Some(file) = plot {
+        let mut out = File::create(file).unwrap();
+        out.write_all(plot_svg(&recorder, &names, &stages).as_bytes()).unwrap();
+    }
 }
@@ -0,0 +1,14 @@
+extern crate backend;
+
+use std::env::args;
+use backend::*;
+
+fn main() {
+    let args: Vec<String> = args().collect();
+    if args.len() != 2 {
+        println!("Usage: {} <genome>", args[0]);
+        return
+    }
+    let (genome, names) = load_genome(&args[1]).unwrap();
+    print!("{}", genome.analyze().report(&names));
+}
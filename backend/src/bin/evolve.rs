@@ -1,52 +1,202 @@
 extern crate backend;
+extern crate rand;
 
 use std::cmp::Ordering;
-use std::iter::repeat;
+use std::collections::HashMap;
+use std::env::args;
 use backend::*;
+use rand::{thread_rng, Rng};
+
+const POPULATION: usize = 100;
+const GENERATIONS: usize = 200;
+const CAP: u32 = 1000;
+const SPECIES_THRESHOLD: f32 = 0.3;
+/// The tick creatures should become adults at.
+const ADULTHOOD: u32 = 300;
+/// How many creatures a world starts with, how long it runs in natural selection and how often
+/// its population is counted.
+const FOUNDERS: usize = 20;
+const TICKS: u64 = 20000;
+const CENSUS: u64 = 500;
+/// More creatures than the food could ever feed, which means they have learned to make their own.
+const OVERRUN: usize = 5000;
+/// A creature that gets hungry as its food runs low, and wants to breed while well fed.
+const SEED: &'static str = "
+chemical 1 food
+chemical 2 pregnancy
+clock 50 50 50 100 200 100
+initial food 100
+receptor analogue chem food > 0 -> locus hunger nominal 255 gain 255 invert
+receptor digital chem food > 100 -> locus sex_drive nominal 0 gain 255
+female receptor digital chem food > 100 -> locus fertility nominal 0 gain 255
+";
+
+fn usage(program: &str) {
+    println!("Usage: {} [--natural [genome]]", program);
+    println!("With --natural, a population evolves in a world with finite food instead of being");
+    println!("selected for a fitness, starting from the given genome or a simple seed. The");
+    println!("chemicals the genome names food and pregnancy are eaten and carry pregnancies.");
+}
 
 fn main() {
-    let target = 0;
-    let mut fit = Fitness(std::u32::MAX, Genome::new());
-    while fit.0 != 0 {
-        fit = evolve(fit.1, 1000, 1000, |ticks: u32, genome| {
-            Fitness((target as i64 - ticks as i64).abs() as u32, genome)
-        });
-    }
-    fit.1.save("evolved.json").unwrap();
+    let args: Vec<String> = args().collect();
+    match (args.get(1).map(|arg| &arg[..]), args.get(2), args.len()) {
+        (None, _, _) => (),
+        (Some("--natural"), path, n) if n <= 3 => return natural(path),
+        _ => return usage(&args[0]),
+    }
+    // Grow up on schedule, stay alive as long as possible and keep hunger low.
+    let mut pedigree = Pedigree::new();
+    let base = Genome::from_genes(vec![Gene::Clock(LifeClock::default())]);
+    let population = evolve(base, GENERATIONS, CAP, &mut pedigree, |aged, lived, hunger| {
+        vec![(ADULTHOOD as i64 - aged as i64).abs() as f32, (CAP - lived) as f32, hunger]
+    });
+    let points: Vec<Objectives> = population.iter().map(|fit| fit.0.clone()).collect();
+    let front = &non_dominated_sort(&points)[0];
+    println!("Pareto front after {} generations (aging error, early death, mean hunger):",
+             GENERATIONS);
+    for &n in front.iter() {
+        println!("  {:?}", population[n].0);
+    }
+    let best = front.iter().min_by(|&&a, &&b| {
+        population[a].0[0].partial_cmp(&population[b].0[0]).unwrap_or(Ordering::Equal)
+    }).unwrap();
+    population[*best].1.save("evolved.json").unwrap();
+    pedigree.prune(&[population[*best].1.lineage().id]);
+    pedigree.save("pedigree.json").unwrap();
 }
 
-fn evolve<F>(base: Genome, trials: usize, cap: u32, fitness: F) -> Fitness
-where F: Fn(u32, Genome) -> Fitness {
-    repeat(base).take(trials).map(|genome| {
-        let genome = genome.mutate().mutate().mutate();
-        let mut creature = Creature::new();
-        genome.init(&mut creature);
-        for t in 0 .. cap {
-            genome.step(&mut creature);
-            if creature.age() == Age::Baby { return fitness(t, genome) }
+fn evolve<F>(base: Genome, generations: usize, cap: u32, pedigree: &mut Pedigree, fitness: F)
+             -> Vec<Fitness> where F: Fn(u32, u32, f32) -> Objectives {
+    let mut rng = thread_rng();
+    let mut speciation = Speciation::new(SPECIES_THRESHOLD);
+    let mut population = evaluate((0 .. POPULATION).map(|_| base.offspring().mutate()).collect(),
+                                  cap, &fitness);
+    pedigree.record(&base);
+    for _ in 0 .. generations {
+        for fit in population.iter() {
+            pedigree.record(&fit.1);
         }
-        fitness(cap, genome)
-    }).min().unwrap()
+        speciation.speciate(population.iter().map(|fit| &fit.1));
+        let points: Vec<Objectives> = population.iter().map(|fit| fit.0.clone()).collect();
+        let ranks = rank(&speciation.share(&points));
+        let children: Vec<Genome> = (0 .. POPULATION).map(|_| {
+            let a = rng.gen_range(0, population.len());
+            let b = rng.gen_range(0, population.len());
+            let parent = if ranks[a].crowded_cmp(&ranks[b]) == Ordering::Greater { b } else { a };
+            population[parent].1.offspring().mutate_with(Validation::Reject)
+                                .mutate_with(Validation::Reject)
+                                .mutate_with(Validation::Reject)
+        }).collect();
+        population.extend(evaluate(children, cap, &fitness));
+        let points: Vec<Objectives> = population.iter().map(|fit| fit.0.clone()).collect();
+        let mut survivors: Vec<Option<Fitness>> = population.into_iter().map(Some).collect();
+        population = select(&points, POPULATION).into_iter().map(|n| {
+            survivors[n].take().unwrap()
+        }).collect();
+    }
+    for fit in population.iter() {
+        pedigree.record(&fit.1);
+    }
+    println!("{} species remain in the final population.", speciation.species().len());
+    population
 }
 
-struct Fitness(u32, Genome);
-
-impl PartialEq for Fitness {
-    fn eq(&self, other: &Fitness) -> bool {
-        self.0 == other.0
+/// Raises a creature from each genome, all in one population, for up to `cap` ticks or until it
+/// dies. Returns their fitness in the order the genomes were given.
+fn evaluate<F>(genomes: Vec<Genome>, cap: u32, fitness: &F) -> Vec<Fitness>
+where F: Fn(u32, u32, f32) -> Objectives {
+    let mut population = Population::new();
+    let ids: Vec<CreatureId> = genomes.into_iter().map(|genome| population.birth(genome)).collect();
+    // When each creature became an adult, and its hunger summed over every tick it lived.
+    let mut stats: HashMap<CreatureId, (u32, u64)> = HashMap::new();
+    let mut done = Vec::new();
+    for _ in 0 .. cap {
+        if population.is_empty() { break }
+        population.step();
+        for member in population.members() {
+            let creature = &member.creature;
+            let stat = stats.entry(member.id).or_insert((cap, 0));
+            if stat.0 == cap && creature.age() >= Age::Adult {
+                stat.0 = creature.lived() as u32 - 1;
+            }
+            stat.1 += creature.get_drive(Drive::Hunger) as u64;
+        }
+        done.extend(population.remove_dead());
     }
+    done.extend(population.remove_unless(|_| false));
+    let mut scored: HashMap<CreatureId, Fitness> = done.into_iter().map(|member| {
+        let (aged, hunger) = stats.get(&member.id).cloned().unwrap_or((cap, 0));
+        let lived = member.creature.lived() as u32;
+        let fit = fitness(aged, lived, hunger as f32 / lived.max(1) as f32);
+        (member.id, Fitness(fit, member.genome))
+    }).collect();
+    ids.iter().map(|id| scored.remove(id).unwrap()).collect()
 }
 
-impl Eq for Fitness {}
+struct Fitness(Objectives, Genome);
 
-impl PartialOrd for Fitness {
-    fn partial_cmp(&self, other: &Fitness) -> Option<Ordering> {
-        self.0.partial_cmp(&other.0)
+/// Lets a population of the seed genome, or the genome at `path`, live off a finite food supply,
+/// reporting on it every `CENSUS` ticks. Saves the genome of the latest generation left.
+fn natural(path: Option<&String>) {
+    let (seed, names) = match path {
+        Some(path) => load_genome(path).unwrap(),
+        None => parse_genome(SEED).unwrap(),
+    };
+    let env = Environment {
+        food: names.lookup_chemical("food").unwrap_or(Environment::default().food),
+        .. Environment::default()
+    };
+    let mut pedigree = Pedigree::new();
+    pedigree.record(&seed);
+    let mut population = Population::new();
+    for _ in 0 .. FOUNDERS {
+        let genome = seed.offspring().mutate_with(Validation::Reject);
+        pedigree.record(&genome);
+        population.birth(genome);
     }
-}
-
-impl Ord for Fitness {
-    fn cmp(&self, other: &Fitness) -> Ordering {
-        self.0.cmp(&other.0)
+    // Carry children for half as long as usual, so that a pair can more than replace itself.
+    let breeding = Breeding {
+        gestation: 4,
+        hormone: names.lookup_chemical("pregnancy").unwrap_or(Breeding::default().hormone),
+        .. Breeding::default()
+    };
+    let users = breeding.hormone_users(&seed);
+    if !users.is_empty() {
+        println!("Warning: genes {:?} use chemical {}, which is the pregnancy hormone.", users,
+                 breeding.hormone);
+    }
+    let mut world = World::new(population, env, breeding);
+    println!("{:>6} {:>5} {:>5} {:>7} {:>5} {:>5} {:>6} {:>6} {:>5}",
+             "tick", "alive", "born", "starved", "died", "food", "gen", "latest", "genes");
+    while world.tick() < TICKS && !world.population().is_empty() {
+        for (_, _, event) in world.step() {
+            if let Event::GaveBirth { child } = event {
+                if let Some(child) = world.population().get(child) {
+                    pedigree.record(&child.genome);
+                }
+            }
+        }
+        if world.tick() % CENSUS == 0 || world.population().is_empty() {
+            let c = world.census();
+            println!("{:>6} {:>5} {:>5} {:>7} {:>5} {:>5} {:>6.1} {:>6} {:>5.1}", c.tick, c.alive,
+                     c.born, c.starved, c.died, c.food, c.mean_generation, c.max_generation,
+                     c.mean_genes);
+        }
+        if world.population().len() > OVERRUN {
+            println!("Stopping at t = {}: the population no longer depends on food.", world.tick());
+            break
+        }
+    }
+    let latest = world.population().members().iter().max_by_key(|m| {
+        m.genome.lineage().generation
+    });
+    match latest {
+        Some(member) => {
+            member.genome.save("evolved.json").unwrap();
+            pedigree.prune(&[member.genome.lineage().id]);
+            pedigree.save("pedigree.json").unwrap();
+        },
+        None => println!("The population died out."),
     }
 }
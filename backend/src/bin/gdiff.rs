@@ -0,0 +1,26 @@
+extern crate backend;
+
+use std::env::args;
+use backend::*;
+
+fn main() {
+    let args: Vec<String> = args().collect();
+    if args.len() == 5 && args[1] == "--apply" {
+        let patch = GenomeDiff::load(&args[2]).unwrap();
+        let genome = Genome::load(&args[3]).unwrap();
+        match patch.apply(&genome) {
+            Ok(patched) => patched.save(&args[4]).unwrap(),
+            Err(conflict) => println!("Failed to apply patch: {}.", conflict),
+        }
+    } else if args.len() == 3 || args.len() == 4 {
+        let diff = GenomeDiff::between(&Genome::load(&args[1]).unwrap(),
+                                       &Genome::load(&args[2]).unwrap());
+        print!("{}", diff);
+        if let Some(path) = args.get(3) {
+            diff.save(path).unwrap();
+        }
+    } else {
+        println!("Usage: {} <old.json> <new.json> [patch.json]", args[0]);
+        println!("       {} --apply <patch.json> <genome.json> <out.json>", args[0]);
+    }
+}
@@ -0,0 +1,20 @@
+extern crate backend;
+
+use std::env::args;
+use backend::*;
+
+fn main() {
+    let args: Vec<String> = args().collect();
+    if args.len() < 2 || args.len() > 3 || (args.len() == 3 && args[2] != "--graphml") {
+        println!("Usage: {} <genome> [--graphml]", args[0]);
+        println!("Only the bloodstream is drawn: organs and the genes inside them are left out.");
+        return
+    }
+    let (genome, names) = load_genome(&args[1]).unwrap();
+    let network = Network::from_genome(&genome);
+    if args.len() == 3 {
+        print!("{}", network.to_graphml(&names));
+    } else {
+        print!("{}", network.to_dot(&names));
+    }
+}
@@ -0,0 +1,27 @@
+extern crate backend;
+
+use std::env::args;
+use std::fs::File;
+use std::io::prelude::*;
+use backend::*;
+
+fn main() {
+    let args: Vec<String> = args().collect();
+    if args.len() == 4 && args[1] == "--compile" {
+        let mut text = String::new();
+        File::open(&args[2]).unwrap().read_to_string(&mut text).unwrap();
+        match parse_genome(&text) {
+            Ok((genome, _)) => genome.save(&args[3]).unwrap(),
+            Err(e) => println!("{}: {}", args[2], e),
+        }
+    } else if args.len() == 2 {
+        let genome = Genome::load(&args[1]).unwrap();
+        print!("{}", print_genome(&genome, &Names::new()));
+        for diagnostic in genome.validate() {
+            println!("# {}", diagnostic);
+        }
+    } else {
+        println!("Usage: {} <genome.json>", args[0]);
+        println!("       {} --compile <genome.txt> <genome.json>", args[0]);
+    }
+}
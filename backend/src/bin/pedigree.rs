@@ -0,0 +1,29 @@
+extern crate backend;
+
+use std::env::args;
+use backend::*;
+
+fn main() {
+    let args: Vec<String> = args().collect();
+    if args.len() < 3 {
+        println!("Usage: {} <pedigree.json> <genome.json> [gene index]", args[0]);
+        return
+    }
+    let mut pedigree = Pedigree::load(&args[1]).unwrap();
+    let genome = Genome::load(&args[2]).unwrap();
+    pedigree.record(&genome);
+    print!("{}", pedigree.render());
+    if let Some(index) = args.get(3).and_then(|s| s.parse().ok()) {
+        let history = pedigree.trace_gene(&genome, index);
+        match history.first() {
+            Some(&(lineage, Mutation::Inserted(_))) => {
+                println!("Gene {} first appeared in generation {} ({:016x}).", index,
+                         lineage.generation, lineage.id);
+            },
+            _ => println!("Gene {} predates the recorded pedigree.", index),
+        }
+        for &(lineage, mutation) in history.iter() {
+            println!("  {} in generation {} ({:016x})", mutation, lineage.generation, lineage.id);
+        }
+    }
+}
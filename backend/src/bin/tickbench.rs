@@ -0,0 +1,158 @@
+extern crate backend;
+extern crate rand;
+
+use std::collections::HashMap;
+use std::env::args;
+use std::time::Instant;
+use backend::*;
+use rand::{thread_rng, Rng};
+
+/// How many genes each genome benchmarked by default has.
+const GENES: usize = 200;
+const GENOMES: usize = 10;
+const TICKS: u32 = 20000;
+
+/// Chemical storage as it was before `ChemoBody` became a dense array, where even reads insert.
+struct HashBody {
+    chems: HashMap<Id, Chemical>,
+}
+
+impl HashBody {
+    fn concnt(&mut self, id: Id) -> u8 {
+        self.chems.entry(id).or_insert(Chemical::new(id)).concnt()
+    }
+
+    fn gain(&mut self, id: Id, amount: Concentration) {
+        let val = self.chems.entry(id).or_insert(Chemical::new(id));
+        *val = Chemical::with_concentration(id, val.concnt().saturating_add(amount));
+    }
+
+    fn lose(&mut self, id: Id, amount: Concentration) {
+        let val = self.chems.entry(id).or_insert(Chemical::new(id));
+        if let Some(new) = val.concnt().checked_sub(amount) {
+            *val = Chemical::with_concentration(id, new);
+        }
+    }
+}
+
+/// The chemical reads and writes a tick of `genome` makes, without any of the arithmetic.
+fn access_pattern(genome: &Genome) -> Vec<(bool, Id)> {
+    let mut pattern = Vec::new();
+    for gene in genome.genes().iter() {
+        match *gene {
+            Gene::Emitter(ref e) => pattern.push((true, e.chemical)),
+            Gene::Reaction(ref r) => {
+                pattern.extend(r.kind.reactants().iter().map(|c| (false, c.id)));
+                pattern.extend(r.kind.reactants().iter().map(|c| (true, c.id)));
+                pattern.extend(r.kind.products().iter().map(|c| (true, c.id)));
+            },
+            Gene::Receptor(ref r) => pattern.push((false, r.chemical)),
+            _ => (),
+        }
+    }
+    pattern
+}
+
+fn per_second(ticks: u32, start: Instant) -> f64 {
+    let elapsed = start.elapsed();
+    ticks as f64 / (elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 * 1e-9)
+}
+
+/// Ticks a second of `genome`'s chemical accesses against the old and the new storage.
+fn storage_rates(genome: &Genome) -> (f64, f64) {
+    let pattern = access_pattern(genome);
+    let mut checksum = 0u64;
+    let mut hashed = HashBody { chems: HashMap::new() };
+    let start = Instant::now();
+    for _ in 0 .. TICKS {
+        for &(write, id) in pattern.iter() {
+            if write {
+                hashed.gain(id, 1);
+                hashed.lose(id, 1);
+            } else {
+                checksum += hashed.concnt(id) as u64;
+            }
+        }
+    }
+    let before = per_second(TICKS, start);
+    let mut dense = ChemoBody::new();
+    let start = Instant::now();
+    for _ in 0 .. TICKS {
+        for &(write, id) in pattern.iter() {
+            if write {
+                dense.gain(id, 1);
+                dense.lose(id, 1);
+            } else {
+                checksum += dense.concnt(id) as u64;
+            }
+        }
+    }
+    let after = per_second(TICKS, start);
+    // Keeps the loops from being optimised away.
+    if checksum == u64::max_value() { println!("{}", checksum) }
+    (before, after)
+}
+
+/// Ticks a second of `genome` in `mode`, on a creature that starts afresh whenever it dies.
+fn rate(genome: &Genome, mode: TickMode) -> f64 {
+    let mut creature = Creature::new();
+    genome.init(&mut creature);
+    let start = Instant::now();
+    for _ in 0 .. TICKS {
+        genome.tick(&mut creature, mode);
+        if !creature.is_alive() {
+            creature = Creature::new();
+            genome.init(&mut creature);
+        }
+    }
+    per_second(TICKS, start)
+}
+
+/// A genome of random genes that pass validation, as the genomes bred by rejecting broken
+/// mutations are.
+fn random_genome() -> Genome {
+    let mut rng = thread_rng();
+    let mut genome = Genome::new();
+    while genome.genes().len() < GENES {
+        genome.insert_gene(rng.gen());
+        let last = genome.genes().len() - 1;
+        if genome.validate().iter().any(|d| d.severity == Severity::Error && d.gene == last) {
+            genome.remove_gene(last);
+        }
+    }
+    genome
+}
+
+fn main() {
+    let paths: Vec<String> = args().skip(1).collect();
+    let genomes: Vec<(String, Genome)> = if paths.is_empty() {
+        (0 .. GENOMES).map(|n| (format!("random #{}", n), random_genome())).collect()
+    } else {
+        paths.into_iter().map(|path| {
+            let genome = load_genome(&path).unwrap().0;
+            (path, genome)
+        }).collect()
+    };
+    // HashMap storage, dense storage, sequential ticks and double-buffered ticks.
+    let mut totals = [0.0; 4];
+    for &(ref name, ref genome) in genomes.iter() {
+        let (before, after) = storage_rates(genome);
+        let rates = [before, after, rate(genome, TickMode::Sequential),
+                     rate(genome, TickMode::DoubleBuffered)];
+        println!("{}: {} genes", name, genome.genes().len());
+        print_rates(&rates);
+        for (total, rate) in totals.iter_mut().zip(rates.iter()) {
+            *total += *rate;
+        }
+    }
+    let count = genomes.len() as f64;
+    println!("Mean:");
+    print_rates(&[totals[0] / count, totals[1] / count, totals[2] / count, totals[3] / count]);
+}
+
+fn print_rates(rates: &[f64; 4]) {
+    println!("  HashMap storage: {:.0} ticks/s", rates[0]);
+    println!("  Dense storage:   {:.0} ticks/s", rates[1]);
+    println!("  Full genome:     {:.0} ticks/s sequential, {:.0} ticks/s double-buffered",
+             rates[2], rates[3]);
+}
@@ -0,0 +1,118 @@
+use chem::{Concentration, Id};
+use creature::{Age, Locus, LocusValue, Sex};
+use events::Event;
+use genome::Genome;
+use population::{CreatureId, Population};
+use rand::{thread_rng, Rng};
+use validate::Validation;
+
+/// A child being carried, conceived with `father`. Its sex is settled at conception.
+pub struct Pregnancy {
+    pub father: CreatureId,
+    pub child: Genome,
+    pub sex: Sex,
+}
+
+/// How creatures in a population mate and give birth. A living creature of at least `maturity`
+/// whose sex drive is at least `sex_drive` looks for a mate, and of a mating pair the first with
+/// a fertility of at least `fertility` conceives. Pregnant creatures fill with `hormone`,
+/// `gestation` units a tick, and give birth once it saturates, so genes can both sense and speed
+/// up or slow down a pregnancy. The hormone should be a chemical the genomes bred do not
+/// otherwise use, see `hormone_users`.
+#[derive(Copy, Clone, Debug)]
+pub struct Breeding {
+    pub maturity: Age,
+    pub sex_drive: LocusValue,
+    pub fertility: LocusValue,
+    pub hormone: Id,
+    pub gestation: Concentration,
+    /// How many mutations each child's genome gets on top of crossover.
+    pub mutations: usize,
+    /// Whether only a male and a female can mate, in which case the female carries the child.
+    pub opposite_sexes: bool,
+}
+
+impl Default for Breeding {
+    fn default() -> Breeding {
+        Breeding {
+            maturity: Age::Adult, sex_drive: 128, fertility: 128, hormone: 255, gestation: 2,
+            mutations: 1, opposite_sexes: true
+        }
+    }
+}
+
+impl Breeding {
+    /// The genes of `genome` that use the hormone, which would interfere with pregnancies.
+    pub fn hormone_users(&self, genome: &Genome) -> Vec<usize> {
+        genome.genes().iter().enumerate().filter(|&(_, gene)| {
+            gene.chemicals().contains(&self.hormone)
+        }).map(|(n, _)| n).collect()
+    }
+}
+
+impl Population {
+    /// Moves every pregnancy along, gives birth to any child that is due and mates creatures
+    /// that want to. Returns the ids of the children born.
+    pub fn breed(&mut self, breeding: &Breeding) -> Vec<CreatureId> {
+        let mut due = Vec::new();
+        for member in self.members_mut().iter_mut() {
+            let creature = &mut member.creature;
+            if !creature.is_alive() || creature.pregnancy().is_none() { continue }
+            creature.chemo_body_mut().gain(breeding.hormone, breeding.gestation);
+            if creature.chemo_body().concnt(breeding.hormone) == 255 {
+                creature.chemo_body_mut().lose(breeding.hormone, 255);
+                due.push((member.id, creature.deliver().unwrap()));
+            }
+        }
+        let born: Vec<CreatureId> = due.into_iter().map(|(mother, pregnancy)| {
+            let child = self.birth_as(pregnancy.child, pregnancy.sex);
+            if let Some(mother) = self.get_mut(mother) {
+                mother.creature.emit(Event::GaveBirth { child: child });
+            }
+            child
+        }).collect();
+
+        let mut willing: Vec<usize> = self.members().iter().enumerate().filter(|&(_, m)| {
+            let creature = &m.creature;
+            creature.is_alive() && creature.pregnancy().is_none() &&
+                creature.age() >= breeding.maturity &&
+                creature.get_locus(Locus::SexDrive as u8) >= breeding.sex_drive
+        }).map(|(n, _)| n).collect();
+        // Mates are found at random, not by who happens to be next to whom.
+        thread_rng().shuffle(&mut willing);
+        let pairs: Vec<[usize; 2]> = if breeding.opposite_sexes {
+            let of = |sex| willing.iter().cloned().filter(|&n| {
+                self.members()[n].creature.sex() == sex
+            }).collect::<Vec<usize>>();
+            of(Sex::Female).into_iter().zip(of(Sex::Male)).map(|(f, m)| [f, m]).collect()
+        } else {
+            willing.chunks(2).filter(|pair| pair.len() == 2).map(|p| [p[0], p[1]]).collect()
+        };
+        for pair in pairs {
+            let fertile: Vec<bool> = pair.iter().map(|&n| {
+                self.members()[n].creature.get_locus(Locus::Fertility as u8) >= breeding.fertility
+            }).collect();
+            let (mother, father) = if fertile[0] {
+                (pair[0], pair[1])
+            } else if fertile[1] && !breeding.opposite_sexes {
+                (pair[1], pair[0])
+            } else {
+                continue
+            };
+            let mut child = self.members()[mother].genome.crossover(&self.members()[father].genome);
+            for _ in 0 .. breeding.mutations {
+                child = child.mutate_with(Validation::Reject);
+            }
+            let father = self.members()[father].id;
+            let members = self.members_mut();
+            members[mother].creature.conceive(Pregnancy {
+                father: father, child: child, sex: thread_rng().gen()
+            });
+            members[mother].creature.emit(Event::Conceived { father: father });
+            for &n in pair.iter() {
+                members[n].creature.set_locus(Locus::SexDrive as u8, 0);
+            }
+        }
+        born
+    }
+}
@@ -1,54 +1,98 @@
-use std::cell::Cell;
-use std::cmp::min;
-use std::collections::HashMap;
-use creature::{Creature, LocusId, LocusValue};
+use creature::{Body, GeneState, LocusId, LocusValue};
 use rand::{Rand, Rng};
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
 
 pub type Id = u8;
 pub type Concentration = u8;
 
+/// How a `ChemoBody` stores concentrations: in whole units from 0 to 255 as in Creatures, or with
+/// the `float-concentration` feature, in fractions of a unit over the same range.
+#[cfg(not(feature = "float-concentration"))]
+pub type Level = u8;
+#[cfg(feature = "float-concentration")]
+pub type Level = f32;
+
+/// Converts an amount to a stored level, dropping any fraction that cannot be stored.
+#[cfg(not(feature = "float-concentration"))]
+pub fn to_level(amount: f32) -> Level {
+    amount as u8
+}
+
+#[cfg(feature = "float-concentration")]
+pub fn to_level(amount: f32) -> Level {
+    amount
+}
+
+/// The concentration of every chemical, indexed by id.
 pub struct ChemoBody {
-    chems: HashMap<Id, Chemical>
+    chems: [Level; 256],
+}
+
+impl Clone for ChemoBody {
+    fn clone(&self) -> ChemoBody {
+        ChemoBody { chems: self.chems }
+    }
 }
 
 impl ChemoBody {
     pub fn new() -> ChemoBody {
-        ChemoBody { chems: HashMap::new() }
+        ChemoBody { chems: [0 as Level; 256] }
     }
 
-    pub fn get(&mut self, id: Id) -> &Chemical {
-        self.chems.entry(id).or_insert(Chemical::new(id))
+    pub fn get(&self, id: Id) -> Chemical {
+        Chemical::with_concentration(id, self.concnt(id))
     }
 
-    pub fn concnt(&mut self, id: Id) -> u8 {
-        self.chems.entry(id).or_insert(Chemical::new(id)).concnt()
+    /// A chemical's concentration in whole units, rounded down.
+    pub fn concnt(&self, id: Id) -> Concentration {
+        self.chems[id as usize] as Concentration
+    }
+
+    /// A chemical's concentration as precisely as it is stored.
+    pub fn level(&self, id: Id) -> f32 {
+        self.chems[id as usize] as f32
     }
 
     pub fn gain(&mut self, id: Id, amount: Concentration) -> bool {
-        let val = self.chems.entry(id).or_insert(Chemical::new(id));
-        if let Some(new) = val.concnt().checked_add(amount) {
-            *val = Chemical::with_concentration(id, new);
-            true
-        } else if val.concnt() == 255 {
+        self.gain_level(id, amount as f32)
+    }
+
+    pub fn lose(&mut self, id: Id, amount: Concentration) -> bool {
+        self.lose_level(id, amount as f32)
+    }
+
+    /// Adds to a chemical, saturating at 255. Returns false if it saturated.
+    pub fn gain_level(&mut self, id: Id, amount: f32) -> bool {
+        let val = &mut self.chems[id as usize];
+        let new = *val as f32 + to_level(amount) as f32;
+        if new > 255.0 {
+            *val = 255 as Level;
             false
         } else {
-            *val = Chemical::with_concentration(id, 255);
-            false
+            *val = new as Level;
+            true
         }
     }
 
-    pub fn lose(&mut self, id: Id, amount: Concentration) -> bool {
-        let val = self.chems.entry(id).or_insert(Chemical::new(id));
-        if let Some(new) = val.concnt().checked_sub(amount) {
-            *val = Chemical::with_concentration(id, new);
-            true
-        } else {
+    /// Sets a chemical to `level`, kept between 0 and 255.
+    pub fn set_level(&mut self, id: Id, level: f32) {
+        self.chems[id as usize] = to_level(level.max(0.0).min(255.0));
+    }
+
+    /// Takes from a chemical, or returns false and leaves it alone if there is not enough.
+    pub fn lose_level(&mut self, id: Id, amount: f32) -> bool {
+        let val = &mut self.chems[id as usize];
+        let amount = to_level(amount) as f32;
+        if (*val as f32) < amount {
             false
+        } else {
+            *val = (*val as f32 - amount) as Level;
+            true
         }
     }
 }
 
-#[derive(Copy, Clone, RustcEncodable, RustcDecodable)]
+#[derive(Copy, Clone, Debug, RustcEncodable, RustcDecodable)]
 pub struct Chemical {
     id: Id,
     concentration: Concentration,
@@ -78,7 +122,7 @@ impl Chemical {
     }
 }
 
-#[derive(Copy, Clone, RustcEncodable, RustcDecodable)]
+#[derive(Copy, Clone, Debug, RustcEncodable, RustcDecodable)]
 pub enum IoType {
     Analogue,
     Digital,
@@ -94,27 +138,6 @@ impl Rand for IoType {
     }
 }
 
-#[derive(Clone, RustcEncodable, RustcDecodable)]
-pub struct TickCount(Cell<u8>);
-
-impl TickCount {
-    fn new() -> TickCount {
-        TickCount(Cell::new(0))
-    }
-
-    fn inc(&self) {
-        self.0.set(self.0.get() + 1);
-    }
-
-    fn zero(&self) {
-        self.0.set(0);
-    }
-
-    fn val(&self) -> u8 {
-        self.0.get()
-    }
-}
-
 #[derive(Clone, RustcEncodable, RustcDecodable)]
 pub struct Emitter {
     pub kind: IoType,
@@ -125,7 +148,6 @@ pub struct Emitter {
     pub threshold: LocusValue,
     pub clear_after_read: bool,
     pub invert: bool,
-    pub tick: TickCount,
 }
 
 impl Rand for Emitter {
@@ -140,32 +162,32 @@ impl Emitter {
                threshold: LocusValue, clear_after_read: bool, invert: bool) -> Emitter {
         Emitter {
             kind: kind, chemical: chemical, rate: rate, gain: gain, locus: locus,
-            threshold: threshold, clear_after_read: clear_after_read, invert: invert,
-            tick: TickCount::new()
+            threshold: threshold, clear_after_read: clear_after_read, invert: invert
         }
     }
 
-    pub fn step(&self, creature: &mut Creature) {
-        self.tick.inc();
-        if self.tick.val() < self.rate { return }
-        self.tick.zero();
+    pub fn step(&self, state: &mut GeneState, body: &mut Body) {
+        state.tick += 1;
+        if state.tick < self.rate { return }
+        state.tick = 0;
         let signal = if self.invert {
-            255 - creature.get_locus(self.locus)
+            255 - body.get_locus(self.locus)
         } else {
-            creature.get_locus(self.locus)
+            body.get_locus(self.locus)
         };
-        let mut body = creature.chemo_body_mut();
+        state.on = signal >= self.threshold;
+        let body = &mut *body.chem;
         match self.kind {
             IoType::Analogue => {
                 let modifier = self.gain as f32 / 255.0;
                 if signal >= self.threshold {
-                    let output = ((signal - self.threshold) as f32 * modifier) as u8;
-                    body.gain(self.chemical, output);
+                    let output = (signal - self.threshold) as f32 * modifier;
+                    body.gain_level(self.chemical, output);
                 } else {
-                    let output = ((self.threshold - signal) as f32 * modifier) as u8;
-                    if !body.lose(self.chemical, output) {
-                        let concnt = body.concnt(self.chemical);
-                        body.lose(self.chemical, concnt);
+                    let output = (self.threshold - signal) as f32 * modifier;
+                    if !body.lose_level(self.chemical, output) {
+                        let level = body.level(self.chemical);
+                        body.lose_level(self.chemical, level);
                     }
                 }
             },
@@ -176,23 +198,79 @@ impl Emitter {
     }
 }
 
+/// A stoichiometric coefficient. Coefficients are never zero, since reactions divide by them.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Coefficient(u8);
+
+impl Coefficient {
+    pub fn new(value: u8) -> Option<Coefficient> {
+        if value == 0 { None } else { Some(Coefficient(value)) }
+    }
+
+    pub fn get(&self) -> u8 {
+        self.0
+    }
+}
+
+impl Encodable for Coefficient {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_u8(self.0)
+    }
+}
+
+impl Decodable for Coefficient {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Coefficient, D::Error> {
+        let value = try!(d.read_u8());
+        Coefficient::new(value).ok_or_else(|| d.error("reaction coefficients must be non-zero"))
+    }
+}
+
+/// A chemical taking part in a reaction, with the number of units used or made per reaction.
+#[derive(Copy, Clone, Debug, RustcEncodable)]
+pub struct Reagent {
+    pub id: Id,
+    pub coefficient: Coefficient,
+}
+
+impl Decodable for Reagent {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Reagent, D::Error> {
+        d.read_struct("Reagent", 2, |d| {
+            let id = try!(d.read_struct_field("id", 0, Decodable::decode));
+            let coefficient: Option<Coefficient> = try!(d.read_struct_field("coefficient", 1,
+                                                                            Decodable::decode));
+            // Genomes saved before reagents had coefficients call it a concentration.
+            let coefficient = match coefficient {
+                Some(coefficient) => coefficient,
+                None => try!(d.read_struct_field("concentration", 1, Decodable::decode)),
+            };
+            Ok(Reagent { id: id, coefficient: coefficient })
+        })
+    }
+}
+
+impl Reagent {
+    pub fn new(id: Id, coefficient: Coefficient) -> Reagent {
+        Reagent { id: id, coefficient: coefficient }
+    }
+}
+
 #[derive(Copy, Clone, RustcEncodable, RustcDecodable)]
 pub enum ReactionType {
     /// A + B -> C + D
-    Normal(Chemical, Chemical, Chemical, Chemical),
+    Normal(Reagent, Reagent, Reagent, Reagent),
     /// A + B -> C
-    Fusion(Chemical, Chemical, Chemical),
+    Fusion(Reagent, Reagent, Reagent),
     /// A -> nothing
-    Decay(Chemical),
+    Decay(Reagent),
     /// A + B -> A + C
-    Catalytic(Chemical, Chemical, Chemical),
+    Catalytic(Reagent, Reagent, Reagent),
     /// A + B -> A
-    CatalyticBreakdown(Chemical, Chemical),
+    CatalyticBreakdown(Reagent, Reagent),
 }
 
 impl Rand for ReactionType {
     fn rand<R: Rng>(rng: &mut R) -> ReactionType {
-        let chem = |rng: &mut R| Chemical::with_concentration(rng.gen(), rng.gen_range(1, 17));
+        let chem = |rng: &mut R| Reagent::new(rng.gen(), Coefficient(rng.gen_range(1, 17)));
         match rng.gen_range(0, 5) {
             1 => ReactionType::Normal(chem(rng), chem(rng), chem(rng), chem(rng)),
             2 => ReactionType::Fusion(chem(rng), chem(rng), chem(rng)),
@@ -203,35 +281,157 @@ impl Rand for ReactionType {
     }
 }
 
-#[derive(Clone, RustcEncodable, RustcDecodable)]
+impl ReactionType {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ReactionType::Normal(..) => "Normal",
+            ReactionType::Fusion(..) => "Fusion",
+            ReactionType::Decay(..) => "Decay",
+            ReactionType::Catalytic(..) => "Catalytic",
+            ReactionType::CatalyticBreakdown(..) => "CatalyticBreakdown",
+        }
+    }
+
+    /// Every reagent named by the reaction, in declaration order.
+    pub fn reagents(&self) -> Vec<Reagent> {
+        match *self {
+            ReactionType::Normal(a, b, c, d) => vec![a, b, c, d],
+            ReactionType::Fusion(a, b, c) => vec![a, b, c],
+            ReactionType::Decay(a) => vec![a],
+            ReactionType::Catalytic(a, b, c) => vec![a, b, c],
+            ReactionType::CatalyticBreakdown(a, b) => vec![a, b],
+        }
+    }
+
+    /// The left-hand side of the reaction.
+    pub fn reactants(&self) -> Vec<Reagent> {
+        match *self {
+            ReactionType::Normal(a, b, _, _) => vec![a, b],
+            ReactionType::Fusion(a, b, _) => vec![a, b],
+            ReactionType::Decay(a) => vec![a],
+            ReactionType::Catalytic(a, b, _) => vec![a, b],
+            ReactionType::CatalyticBreakdown(a, b) => vec![a, b],
+        }
+    }
+
+    /// The right-hand side of the reaction, including any catalyst.
+    pub fn products(&self) -> Vec<Reagent> {
+        match *self {
+            ReactionType::Normal(_, _, c, d) => vec![c, d],
+            ReactionType::Fusion(_, _, c) => vec![c],
+            ReactionType::Decay(_) => vec![],
+            ReactionType::Catalytic(a, _, c) => vec![a, c],
+            ReactionType::CatalyticBreakdown(a, _) => vec![a],
+        }
+    }
+
+    /// The same reaction with every reagent passed through `f`.
+    pub fn map<F: Fn(Reagent) -> Reagent>(&self, f: F) -> ReactionType {
+        match *self {
+            ReactionType::Normal(a, b, c, d) => ReactionType::Normal(f(a), f(b), f(c), f(d)),
+            ReactionType::Fusion(a, b, c) => ReactionType::Fusion(f(a), f(b), f(c)),
+            ReactionType::Decay(a) => ReactionType::Decay(f(a)),
+            ReactionType::Catalytic(a, b, c) => ReactionType::Catalytic(f(a), f(b), f(c)),
+            ReactionType::CatalyticBreakdown(a, b) => ReactionType::CatalyticBreakdown(f(a), f(b)),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub enum Kinetics {
+    /// Whenever the reaction fires, as many whole units react as the reactants allow.
+    AllOrNothing,
+    /// Whenever the reaction fires, it proceeds in proportion to this rate constant and to each
+    /// reactant's concentration raised to its coefficient, taking concentrations as fractions of
+    /// 255. The product is scaled by `MASS_ACTION_SCALE` to give units of the reaction, and
+    /// fractional units carry over to the next firing.
+    MassAction(f32),
+}
+
+/// How many units a mass-action reaction with a rate constant of 1 makes each time it fires with
+/// all its reactants at 255.
+pub const MASS_ACTION_SCALE: f32 = 255.0;
+
+impl Rand for Kinetics {
+    fn rand<R: Rng>(rng: &mut R) -> Kinetics {
+        // Most reactions in a genome are all-or-nothing, so mutation mostly makes those.
+        if rng.gen_weighted_bool(8) {
+            Kinetics::MassAction(rng.gen())
+        } else {
+            Kinetics::AllOrNothing
+        }
+    }
+}
+
+#[derive(Clone, RustcEncodable)]
 pub struct Reaction {
     pub kind: ReactionType,
     pub rate: u8,
-    pub tick: TickCount,
+    pub kinetics: Kinetics,
+}
+
+impl Decodable for Reaction {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Reaction, D::Error> {
+        d.read_struct("Reaction", 3, |d| {
+            let kind = try!(d.read_struct_field("kind", 0, Decodable::decode));
+            let rate = try!(d.read_struct_field("rate", 1, Decodable::decode));
+            // Reactions saved before kinetics could be chosen were all-or-nothing.
+            let kinetics: Option<Kinetics> = try!(d.read_struct_field("kinetics", 2,
+                                                                      Decodable::decode));
+            Ok(Reaction::with_kinetics(kind, rate, kinetics.unwrap_or(Kinetics::AllOrNothing)))
+        })
+    }
 }
 
 impl Rand for Reaction {
     fn rand<R: Rng>(rng: &mut R) -> Reaction {
-        Reaction::new(rng.gen(), rng.gen())
+        Reaction::with_kinetics(rng.gen(), rng.gen(), rng.gen())
     }
 }
 
 impl Reaction {
     pub fn new(kind: ReactionType, rate: u8) -> Reaction {
-        Reaction { kind: kind, rate: rate, tick: TickCount::new() }
+        Reaction::with_kinetics(kind, rate, Kinetics::AllOrNothing)
+    }
+
+    pub fn with_kinetics(kind: ReactionType, rate: u8, kinetics: Kinetics) -> Reaction {
+        Reaction { kind: kind, rate: rate, kinetics: kinetics }
+    }
+
+    /// How many units of the reaction happen when it fires, never more than the reactants allow.
+    fn units(&self, body: &ChemoBody, carry: &mut f32) -> u8 {
+        let reactants = self.kind.reactants();
+        let available = reactants.iter().map(|r| body.concnt(r.id) / r.coefficient.get())
+                                 .min().unwrap_or(0);
+        match self.kinetics {
+            Kinetics::AllOrNothing => available,
+            Kinetics::MassAction(k) => {
+                let rate = reactants.iter().fold(k * MASS_ACTION_SCALE, |acc, r| {
+                    acc * (body.level(r.id) / 255.0).powi(r.coefficient.get() as i32)
+                });
+                let units = rate + *carry;
+                if units >= available as f32 {
+                    *carry = 0.0;
+                    available
+                } else {
+                    *carry = units.fract();
+                    units as u8
+                }
+            },
+        }
     }
 
-    pub fn step(&self, creature: &mut Creature) {
-        self.tick.inc();
-        if self.tick.val() < self.rate { return }
-        self.tick.zero();
-        let mut body = creature.chemo_body_mut();
+    pub fn step(&self, state: &mut GeneState, body: &mut Body) {
+        state.tick += 1;
+        if state.tick < self.rate { return }
+        state.tick = 0;
+        let body = &mut *body.chem;
+        let n = self.units(body, &mut state.carry);
+        state.on = n > 0;
         match self.kind {
             ReactionType::Normal(ref a, ref b, ref c, ref d) => {
-                let n = min(body.concnt(a.id) / a.concnt(),
-                            body.concnt(b.id) / b.concnt());
-                let mut update = |c: &Chemical, add: bool| {
-                    let larger = n as u16 * c.concnt() as u16;
+                let mut update = |c: &Reagent, add: bool| {
+                    let larger = n as u16 * c.coefficient.get() as u16;
                     let value = if larger > 255 {
                         255
                     } else {
@@ -249,10 +449,8 @@ impl Reaction {
                 update(d, true);
             },
             ReactionType::Fusion(ref a, ref b, ref c) => {
-                let n = min(body.concnt(a.id) / a.concnt(),
-                            body.concnt(b.id) / b.concnt());
-                let mut update = |c: &Chemical, add: bool| {
-                    let larger = n as u16 * c.concnt() as u16;
+                let mut update = |c: &Reagent, add: bool| {
+                    let larger = n as u16 * c.coefficient.get() as u16;
                     let value = if larger > 255 {
                         255
                     } else {
@@ -269,8 +467,7 @@ impl Reaction {
                 update(c, true);
             },
             ReactionType::Decay(ref a) => {
-                let n = body.concnt(a.id) / a.concnt();
-                let larger = n as u16 * a.concnt() as u16;
+                let larger = n as u16 * a.coefficient.get() as u16;
                 let value = if larger > 255 {
                     255
                 } else {
@@ -278,11 +475,9 @@ impl Reaction {
                 };
                 body.lose(a.id, value);
             },
-            ReactionType::Catalytic(ref a, ref b, ref c) => {
-                let n = min(body.concnt(a.id) / a.concnt(),
-                            body.concnt(b.id) / b.concnt());
-                let mut update = |c: &Chemical, add: bool| {
-                    let larger = n as u16 * c.concnt() as u16;
+            ReactionType::Catalytic(_, ref b, ref c) => {
+                let mut update = |c: &Reagent, add: bool| {
+                    let larger = n as u16 * c.coefficient.get() as u16;
                     let value = if larger > 255 {
                         255
                     } else {
@@ -297,10 +492,8 @@ impl Reaction {
                 update(b, false);
                 update(c, true);
             },
-            ReactionType::CatalyticBreakdown(ref a, ref b) => {
-                let n = min(body.concnt(a.id) / a.concnt(),
-                            body.concnt(b.id) / b.concnt());
-                let larger = n as u16 * b.concnt() as u16;
+            ReactionType::CatalyticBreakdown(_, ref b) => {
+                let larger = n as u16 * b.coefficient.get() as u16;
                 let value = if larger > 255 {
                     255
                 } else {
@@ -338,15 +531,15 @@ impl Receptor {
         }
     }
 
-    pub fn step(&self, creature: &mut Creature) {
-        let val = creature.chemo_body_mut().concnt(self.chemical);
+    pub fn step(&self, state: &mut GeneState, body: &mut Body) {
+        let val = body.chem.level(self.chemical);
+        state.on = val > self.threshold as f32;
         let r = if self.invert { -1 } else { 1 };
         let output = match self.kind {
             IoType::Analogue => {
                 let r = r as f32;
                 let modifier = self.gain as f32 / 255.0;
-                let value = self.nominal as f32 + (((val as f32 - self.threshold as f32) * modifier)
-                                                    * r);
+                let value = self.nominal as f32 + (((val - self.threshold as f32) * modifier) * r);
                 if value > 255.0 {
                     255
                 } else if value < 0.0 {
@@ -356,7 +549,7 @@ impl Receptor {
                 }
             },
             IoType::Digital => {
-                let value = if val > self.threshold { self.gain as i16 } else { 0 } * r;
+                let value = if val > self.threshold as f32 { self.gain as i16 } else { 0 } * r;
                 let larger = self.nominal as i16 + value;
                 if larger > 255 {
                     255
@@ -367,6 +560,62 @@ impl Receptor {
                 }
             }
         };
-        creature.set_locus(self.locus, output);
+        body.set_locus(self.locus, output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_serialize::json::decode;
+    use super::*;
+
+    fn reagent(id: Id, coefficient: u8) -> Reagent {
+        Reagent::new(id, Coefficient::new(coefficient).unwrap())
+    }
+
+    #[test]
+    fn mass_action_raises_concentrations_to_their_coefficients() {
+        let kind = ReactionType::Fusion(reagent(1, 1), reagent(2, 2), reagent(3, 1));
+        let reaction = Reaction::with_kinetics(kind, 0, Kinetics::MassAction(1.0));
+        let mut body = ChemoBody::new();
+        body.gain(1, 51);
+        body.gain(2, 102);
+        // 255 * 0.2 * 0.4^2 = 8.16 units, well short of the 51 the reactants allow.
+        let mut carry = 0.0;
+        assert_eq!(reaction.units(&body, &mut carry), 8);
+        assert!((carry - 0.16).abs() < 1e-3);
+    }
+
+    #[test]
+    fn fractional_units_carry_over() {
+        let kind = ReactionType::Decay(reagent(1, 1));
+        let reaction = Reaction::with_kinetics(kind, 0, Kinetics::MassAction(0.002));
+        let mut body = ChemoBody::new();
+        body.gain(1, 255);
+        // Each firing makes 0.51 units, so the second one makes the first whole unit.
+        let mut carry = 0.0;
+        assert_eq!(reaction.units(&body, &mut carry), 0);
+        assert_eq!(reaction.units(&body, &mut carry), 1);
+        assert!((carry - 0.02).abs() < 1e-3);
+        // A reaction limited by its reactants starts afresh.
+        body.lose(1, 255);
+        body.gain(1, 1);
+        carry = 0.999;
+        assert_eq!(reaction.units(&body, &mut carry), 1);
+        assert_eq!(carry, 0.0);
+    }
+
+    #[test]
+    fn zero_coefficients_do_not_decode() {
+        let error = decode::<Reagent>(r#"{"id": 1, "coefficient": 0}"#).err().unwrap();
+        assert!(error.to_string().contains("reaction coefficients must be non-zero"));
+    }
+
+    #[test]
+    fn legacy_concentrations_decode_as_coefficients() {
+        let reagent: Reagent = decode(r#"{"id": 1, "concentration": 3}"#).unwrap();
+        assert_eq!((reagent.id, reagent.coefficient.get()), (1, 3));
+        let reagent: Reagent = decode(r#"{"id": 1, "coefficient": 2}"#).unwrap();
+        assert_eq!((reagent.id, reagent.coefficient.get()), (1, 2));
     }
 }
@@ -1,56 +1,242 @@
-use std::cell::{Ref, RefCell, RefMut};
-use std::collections::HashMap;
-use chem::ChemoBody;
+use std::cmp::{max, min};
+use breeding::Pregnancy;
+use chem::{ChemoBody, Id};
+use death::DeathCause;
+use events::{Event, EventBus};
+use rand::{thread_rng, Rand, Rng};
 
 pub type LocusId = u8;
 pub type LocusValue = u8;
+pub type LifeForce = u8;
+
+/// The running state of a single gene, kept with the creature so that genomes can be shared.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct GeneState {
+    /// Ticks since the gene last fired.
+    pub tick: u8,
+    /// The fraction of a reaction unit left over from the last time a reaction fired.
+    pub carry: f32,
+    /// Whether the gene did anything the last time it fired.
+    pub on: bool,
+}
+
+/// What a gene can act on: the creature's loci and the chemistry of one of its compartments,
+/// borrowed apart from the genes' own state.
+pub struct Body<'a> {
+    pub loci: &'a mut [LocusValue; 256],
+    pub chem: &'a mut ChemoBody,
+    pub sex: Sex,
+}
+
+impl<'a> Body<'a> {
+    pub fn get_locus(&self, id: LocusId) -> LocusValue {
+        self.loci[id as usize]
+    }
+
+    pub fn set_locus(&mut self, id: LocusId, value: LocusValue) {
+        self.loci[id as usize] = value;
+    }
+}
 
 pub struct Creature {
-    loci: RefCell<HashMap<LocusId, LocusValue>>,
-    chem: RefCell<ChemoBody>,
+    loci: [LocusValue; 256],
+    chem: ChemoBody,
+    genes: Vec<GeneState>,
+    organs: Vec<Compartment>,
+    /// How many ticks the creature has lived.
+    lived: u64,
+    events: EventBus,
+    death: Option<DeathCause>,
+    pregnancy: Option<Pregnancy>,
+    sex: Sex,
+}
+
+struct Compartment {
+    chem: ChemoBody,
+    genes: Vec<GeneState>,
+    life: LifeForce,
 }
 
 impl Creature {
+    /// A creature of a random sex.
     pub fn new() -> Creature {
-        Creature { loci: RefCell::new(HashMap::new()), chem: RefCell::new(ChemoBody::new()) }
+        Creature::with_sex(thread_rng().gen())
+    }
+
+    pub fn with_sex(sex: Sex) -> Creature {
+        let mut creature = Creature {
+            loci: [0; 256], chem: ChemoBody::new(), genes: Vec::new(), organs: Vec::new(),
+            lived: 0, events: EventBus::new(), death: None, pregnancy: None, sex: sex
+        };
+        creature.restore_sex_locus();
+        creature
+    }
+
+    /// Sets the `Sex` locus back to match the creature's sex. Genes can write the locus like any
+    /// other, but it is restored at the start of every tick.
+    pub fn restore_sex_locus(&mut self) {
+        let value = if self.sex == Sex::Male { 255 } else { 0 };
+        self.set_locus(Locus::Sex as u8, value);
+    }
+
+    pub fn sex(&self) -> Sex {
+        self.sex
     }
 
     pub fn get_locus(&self, id: LocusId) -> LocusValue {
-        *self.loci.borrow_mut().entry(id).or_insert(0)
+        self.loci[id as usize]
+    }
+
+    pub fn set_locus(&mut self, id: LocusId, value: LocusValue) {
+        self.loci[id as usize] = value;
     }
 
-    pub fn set_locus(&self, id: LocusId, value: LocusValue) {
-        self.loci.borrow_mut().insert(id, value);
+    /// The chemistry of the bloodstream.
+    pub fn chemo_body(&self) -> &ChemoBody {
+        &self.chem
     }
 
-    pub fn chemo_body(&self) -> Ref<ChemoBody> {
-        self.chem.borrow()
+    pub fn chemo_body_mut(&mut self) -> &mut ChemoBody {
+        &mut self.chem
     }
 
-    pub fn chemo_body_mut(&self) -> RefMut<ChemoBody> {
-        self.chem.borrow_mut()
+    /// The chemistry of an organ, or of the bloodstream for `None`.
+    pub fn compartment(&self, organ: Option<usize>) -> &ChemoBody {
+        match organ {
+            Some(organ) => &self.organs[organ].chem,
+            None => &self.chem,
+        }
+    }
+
+    pub fn compartment_mut(&mut self, organ: Option<usize>) -> &mut ChemoBody {
+        match organ {
+            Some(organ) => &mut self.organs[organ].chem,
+            None => &mut self.chem,
+        }
+    }
+
+    /// The bloodstream and an organ's chemistry together, for moving chemicals between them.
+    pub fn exchange(&mut self, organ: usize) -> (&mut ChemoBody, &mut ChemoBody) {
+        (&mut self.chem, &mut self.organs[organ].chem)
+    }
+
+    /// Splits the creature into the body that the genes of a compartment act on and the state of
+    /// those genes, which is grown to hold at least `genes` entries.
+    pub fn express(&mut self, organ: Option<usize>, genes: usize) -> (Body<'_>, &mut [GeneState]) {
+        let (chem, states) = match organ {
+            Some(organ) => {
+                let compartment = &mut self.organs[organ];
+                (&mut compartment.chem, &mut compartment.genes)
+            },
+            None => (&mut self.chem, &mut self.genes),
+        };
+        if states.len() < genes {
+            states.resize(genes, GeneState::default());
+        }
+        (Body { loci: &mut self.loci, chem: chem, sex: self.sex }, &mut states[..])
+    }
+
+    /// Gives the creature another organ at full life force, returning its index.
+    pub fn add_organ(&mut self) -> usize {
+        self.organs.push(Compartment { chem: ChemoBody::new(), genes: Vec::new(), life: 255 });
+        self.organs.len() - 1
+    }
+
+    pub fn organs(&self) -> usize {
+        self.organs.len()
+    }
+
+    /// An organ's life force, which is zero once it has failed or if it does not exist.
+    pub fn life_force(&self, organ: usize) -> LifeForce {
+        self.organs.get(organ).map_or(0, |o| o.life)
+    }
+
+    pub fn set_life_force(&mut self, organ: usize, life: LifeForce) {
+        self.organs[organ].life = life;
     }
 
     fn get(&self, id: Locus) -> LocusValue {
-        *self.loci.borrow_mut().entry(id as u8).or_insert(0)
+        self.get_locus(id as LocusId)
     }
 
+    /// Whether the creature is alive. Once a death has been recorded it is final, whatever the
+    /// `Death` locus is set to afterwards.
     pub fn is_alive(&self) -> bool {
-       self.get(Locus::Death) == 0
+       self.death.is_none() && self.get(Locus::Death) == 0
+    }
+
+    /// Sets the `Death` locus back to 255 if the creature has died, so that genes reading it
+    /// after a later write still see a dead creature.
+    pub fn hold_death(&mut self) {
+        if self.death.is_some() {
+            self.set_locus(Locus::Death as u8, 255);
+        }
+    }
+
+    /// What killed the creature, if it has died of something the genome can tell.
+    pub fn cause_of_death(&self) -> Option<DeathCause> {
+        self.death
+    }
+
+    /// Notes what killed the creature, unless something already has.
+    pub fn record_death(&mut self, cause: DeathCause) {
+        if self.death.is_none() {
+            self.death = Some(cause);
+        }
+    }
+
+    pub fn lived(&self) -> u64 {
+        self.lived
+    }
+
+    pub fn grow_older(&mut self) {
+        self.lived += 1;
+    }
+
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    pub fn events_mut(&mut self) -> &mut EventBus {
+        &mut self.events
+    }
+
+    /// Emits an event as having happened on the current tick.
+    pub fn emit(&mut self, event: Event) {
+        let tick = self.lived;
+        self.events.emit(tick, event);
+    }
+
+    pub fn drain_events(&mut self) -> Vec<(u64, Event)> {
+        self.events.drain()
+    }
+
+    pub fn pregnancy(&self) -> Option<&Pregnancy> {
+        self.pregnancy.as_ref()
     }
 
+    pub fn conceive(&mut self, pregnancy: Pregnancy) {
+        self.pregnancy = Some(pregnancy);
+    }
+
+    /// Ends the creature's pregnancy, returning it so that the child can be born.
+    pub fn deliver(&mut self) -> Option<Pregnancy> {
+        self.pregnancy.take()
+    }
+
+    /// The latest stage whose locus is set.
     pub fn age(&self) -> Age {
-        if self.get(Locus::AgedToSenile) == 0 {
+        if self.get(Locus::AgedToSenile) != 0 {
             Age::Senile
-        } else if self.get(Locus::AgedToOld) == 0 {
+        } else if self.get(Locus::AgedToOld) != 0 {
             Age::Old
-        } else if self.get(Locus::AgedToAdult) == 0 {
+        } else if self.get(Locus::AgedToAdult) != 0 {
             Age::Adult
-        } else if self.get(Locus::AgedToYouth) == 0 {
+        } else if self.get(Locus::AgedToYouth) != 0 {
             Age::Youth
-        } else if self.get(Locus::AgedToAdolescent) == 0 {
+        } else if self.get(Locus::AgedToAdolescent) != 0 {
             Age::Adolescent
-        } else if self.get(Locus::AgedToChild) == 0 {
+        } else if self.get(Locus::AgedToChild) != 0 {
             Age::Child
         } else {
             Age::Baby
@@ -58,14 +244,132 @@ impl Creature {
     }
 
     pub fn get_drive(&self, drive: Drive) -> LocusValue {
-        self.get(match drive {
-            Drive::Hunger => Locus::Hunger
-        })
+        self.get(drive.locus())
+    }
+}
+
+/// The chemistry, loci and organ life forces of a creature, without its gene state.
+struct State {
+    loci: [LocusValue; 256],
+    /// The bloodstream followed by each organ.
+    chem: Vec<ChemoBody>,
+    life: Vec<LifeForce>,
+}
+
+impl State {
+    fn of(creature: &Creature) -> State {
+        let mut chem = vec![creature.chem.clone()];
+        chem.extend(creature.organs.iter().map(|o| o.chem.clone()));
+        let life = creature.organs.iter().map(|o| o.life).collect();
+        State { loci: creature.loci, chem: chem, life: life }
+    }
+}
+
+impl Creature {
+    /// The bloodstream for 0, or organ `n - 1`, as a `State` numbers them.
+    fn nth_compartment(&mut self, n: usize) -> &mut ChemoBody {
+        if n == 0 { &mut self.chem } else { &mut self.organs[n - 1].chem }
+    }
+}
+
+/// Lets genes act as if at the same time. Each gene acts on the creature as it was at the start
+/// of the tick, then its changes are taken out of the creature and kept until the end, when they
+/// are all applied at once. Changes to chemicals and life force add up, except that where genes
+/// together take more of a chemical than there was, each of them only acts in part, so that none
+/// make anything from what another used up. Where several genes write a locus the highest value
+/// wins, and a locus left at its old value does not count as written.
+pub struct DoubleBuffer {
+    previous: State,
+    loci: [Option<LocusValue>; 256],
+    /// What each gene changed: the compartment, numbered as in `State`, the chemical and by how
+    /// much.
+    changes: Vec<Vec<(usize, Id, f32)>>,
+    life: Vec<i16>,
+}
+
+impl DoubleBuffer {
+    pub fn new(creature: &Creature) -> DoubleBuffer {
+        let previous = State::of(creature);
+        DoubleBuffer {
+            loci: [None; 256], changes: Vec::new(), life: vec![0; previous.life.len()],
+            previous: previous
+        }
+    }
+
+    /// Takes whatever a gene has changed since the tick started out of the creature and keeps
+    /// it. Only the gene's own `chemicals` are looked at, since it cannot change any others.
+    pub fn collect(&mut self, creature: &mut Creature, chemicals: &[Id]) {
+        if creature.loci[..] != self.previous.loci[..] {
+            for (id, &old) in self.previous.loci.iter().enumerate() {
+                let value = creature.loci[id];
+                if value != old {
+                    self.loci[id] = Some(self.loci[id].map_or(value, |other| max(other, value)));
+                    creature.loci[id] = old;
+                }
+            }
+        }
+        let mut changes = Vec::new();
+        for (n, previous) in self.previous.chem.iter().enumerate() {
+            let body = creature.nth_compartment(n);
+            for &id in chemicals.iter() {
+                let change = body.level(id) - previous.level(id);
+                if change != 0.0 {
+                    changes.push((n, id, change));
+                    body.set_level(id, previous.level(id));
+                }
+            }
+        }
+        self.changes.push(changes);
+        for (n, (organ, &old)) in creature.organs.iter_mut().zip(self.previous.life.iter())
+                                                          .enumerate() {
+            self.life[n] += organ.life as i16 - old as i16;
+            organ.life = old;
+        }
+    }
+
+    /// Ends the tick, applying every change collected during it.
+    pub fn swap(self, creature: &mut Creature) {
+        for (locus, value) in creature.loci.iter_mut().zip(self.loci.iter()) {
+            *locus = value.unwrap_or(*locus);
+        }
+        // How much of each chemical the genes took between them.
+        let mut drawn: Vec<[f32; 256]> = self.previous.chem.iter().map(|_| [0.0; 256]).collect();
+        for &(n, id, change) in self.changes.iter().flat_map(|c| c.iter()) {
+            if change < 0.0 {
+                drawn[n][id as usize] -= change;
+            }
+        }
+        let previous = &self.previous;
+        let scales: Vec<f32> = self.changes.iter().map(|changes| {
+            changes.iter().filter(|c| c.2 < 0.0).fold(1.0, |scale: f32, &(n, id, _)| {
+                let (drawn, available) = (drawn[n][id as usize], previous.chem[n].level(id));
+                if drawn > available { scale.min(available / drawn) } else { scale }
+            })
+        }).collect();
+        // Everything taken is taken before anything is given, so that nothing saturates early.
+        for &taking in [true, false].iter() {
+            for (changes, &scale) in self.changes.iter().zip(scales.iter()) {
+                for &(n, id, change) in changes.iter().filter(|c| (c.2 < 0.0) == taking) {
+                    let body = creature.nth_compartment(n);
+                    let change = change * scale;
+                    if change > 0.0 {
+                        body.gain_level(id, change);
+                    } else if !body.lose_level(id, -change) {
+                        let level = body.level(id);
+                        body.lose_level(id, level);
+                    }
+                }
+            }
+        }
+        for (organ, &change) in creature.organs.iter_mut().zip(self.life.iter()) {
+            organ.life = max(0, min(255, organ.life as i16 + change)) as LifeForce;
+        }
     }
 }
 
 #[repr(u8)]
-enum Locus {
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Locus {
     Death            = 0,
     AgedToChild      = 1,
     AgedToAdolescent = 2,
@@ -74,9 +378,64 @@ enum Locus {
     AgedToOld        = 5,
     AgedToSenile     = 6,
     Hunger           = 7,
+    SexDrive         = 8,
+    Fertility        = 9,
+    /// 255 for males and 0 for females, restored every tick.
+    Sex              = 10,
+}
+
+impl Locus {
+    pub fn from_id(id: LocusId) -> Option<Locus> {
+        Some(match id {
+            0 => Locus::Death,
+            1 => Locus::AgedToChild,
+            2 => Locus::AgedToAdolescent,
+            3 => Locus::AgedToYouth,
+            4 => Locus::AgedToAdult,
+            5 => Locus::AgedToOld,
+            6 => Locus::AgedToSenile,
+            7 => Locus::Hunger,
+            8 => Locus::SexDrive,
+            9 => Locus::Fertility,
+            10 => Locus::Sex,
+            _ => return None
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Locus::Death => "death",
+            Locus::AgedToChild => "aged_to_child",
+            Locus::AgedToAdolescent => "aged_to_adolescent",
+            Locus::AgedToYouth => "aged_to_youth",
+            Locus::AgedToAdult => "aged_to_adult",
+            Locus::AgedToOld => "aged_to_old",
+            Locus::AgedToSenile => "aged_to_senile",
+            Locus::Hunger => "hunger",
+            Locus::SexDrive => "sex_drive",
+            Locus::Fertility => "fertility",
+            Locus::Sex => "sex",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub enum Sex {
+    Female,
+    Male,
+}
+
+impl Rand for Sex {
+    fn rand<R: Rng>(rng: &mut R) -> Sex {
+        if rng.gen() {
+            Sex::Male
+        } else {
+            Sex::Female
+        }
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Age {
     Baby,
     Child,
@@ -87,6 +446,77 @@ pub enum Age {
     Senile
 }
 
+impl Age {
+    /// The stage numbered `n` from `Baby`, or `Senile` past the end.
+    pub fn from_stage(n: usize) -> Age {
+        match n {
+            0 => Age::Baby,
+            1 => Age::Child,
+            2 => Age::Adolescent,
+            3 => Age::Youth,
+            4 => Age::Adult,
+            5 => Age::Old,
+            _ => Age::Senile,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Drive {
-    Hunger
+    Hunger,
+    Sex,
+}
+
+pub const DRIVES: [Drive; 2] = [Drive::Hunger, Drive::Sex];
+
+impl Drive {
+    pub fn locus(&self) -> Locus {
+        match *self {
+            Drive::Hunger => Locus::Hunger,
+            Drive::Sex => Locus::SexDrive,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dsl::parse_genome;
+    use genome::{Genome, TickMode};
+    use super::*;
+
+    #[test]
+    fn double_buffered_genes_act_in_any_order() {
+        // Both reactions want more of chemical 1 than there is, and both receptors write locus
+        // 40, which the emitter reads.
+        let (genome, _) = parse_genome("initial 1 10
+initial 2 10
+reaction decay 4*1 -> nothing
+reaction normal 3*1 + 1*2 -> 1*3 + 1*4
+receptor digital chem 2 > 0 -> locus 40 nominal 100 gain 0
+receptor digital chem 2 > 0 -> locus 40 nominal 30 gain 0
+emitter analogue locus 40 >= 50 -> +2 chem 5").unwrap();
+        let genes = genome.genes().to_vec();
+        let mut reversed = genes.clone();
+        reversed.reverse();
+        let rotated = genes[3 ..].iter().chain(genes[.. 3].iter()).cloned().collect();
+        let creatures: Vec<Creature> = vec![genes, reversed, rotated].into_iter().map(|genes| {
+            let genome = Genome::from_genes(genes);
+            let mut creature = Creature::with_sex(Sex::Female);
+            genome.init(&mut creature);
+            for _ in 0 .. 3 {
+                genome.tick(&mut creature, TickMode::DoubleBuffered);
+            }
+            creature
+        }).collect();
+        let first = &creatures[0];
+        assert_eq!(first.get_locus(40), 100);
+        assert!(first.chemo_body().level(1) < 10.0);
+        for creature in creatures[1 ..].iter() {
+            assert_eq!(&creature.loci[..], &first.loci[..]);
+            for id in 0 .. 256 {
+                let id = id as Id;
+                assert_eq!(creature.chemo_body().level(id), first.chemo_body().level(id));
+            }
+        }
+    }
 }
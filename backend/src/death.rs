@@ -0,0 +1,126 @@
+use std::collections::VecDeque;
+use chem::Id;
+use creature::{Age, Creature, Drive, LocusValue, DRIVES};
+use dsl::Names;
+use recorder::Observer;
+
+/// What set a creature's `Death` locus.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DeathCause {
+    /// A receptor sensing `chemical`, in an organ's compartment if `organ` is set.
+    Receptor { gene: usize, organ: Option<usize>, chemical: Id },
+    /// A vital organ ran out of life force.
+    OrganFailure(usize),
+    /// The creature went without food for too long in a `World`.
+    Starvation,
+    /// The creature reached the end of its lifespan in a `World`.
+    OldAge,
+    /// The locus was set from outside the genome, such as with `Creature::set_locus`.
+    Unknown,
+}
+
+struct Snapshot {
+    tick: u64,
+    chems: [u8; 256],
+    loci: [LocusValue; 256],
+}
+
+/// Keeps the last few ticks of a creature's bloodstream and loci, to look back on if it dies.
+pub struct History {
+    capacity: usize,
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> History {
+        History { capacity: capacity, snapshots: VecDeque::with_capacity(capacity) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+}
+
+impl Observer for History {
+    fn observe(&mut self, creature: &Creature) {
+        if self.capacity == 0 { return }
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        let mut snapshot = Snapshot { tick: creature.lived(), chems: [0; 256], loci: [0; 256] };
+        for id in 0 .. 256 {
+            snapshot.chems[id] = creature.chemo_body().concnt(id as Id);
+            snapshot.loci[id] = creature.get_locus(id as u8);
+        }
+        self.snapshots.push_back(snapshot);
+    }
+}
+
+/// Why and how a creature died, put together from its recent history.
+#[derive(Clone, Debug)]
+pub struct PostMortem {
+    /// How many ticks the creature lived.
+    pub lived: u64,
+    pub age: Age,
+    pub cause: DeathCause,
+    /// Chemicals at 255 in the bloodstream at death.
+    pub saturated: Vec<Id>,
+    /// Chemicals used up in the bloodstream: present during the history, but gone at death.
+    pub depleted: Vec<Id>,
+    /// The first tick the history covers.
+    pub since: u64,
+    /// Each drive's level at every tick in the history, oldest first.
+    pub drives: Vec<(Drive, Vec<LocusValue>)>,
+}
+
+impl PostMortem {
+    pub fn new(creature: &Creature, history: &History) -> PostMortem {
+        let body = creature.chemo_body();
+        PostMortem {
+            lived: creature.lived(),
+            age: creature.age(),
+            cause: creature.cause_of_death().unwrap_or(DeathCause::Unknown),
+            saturated: (0 .. 256).map(|id| id as Id).filter(|&id| body.concnt(id) == 255).collect(),
+            depleted: (0 .. 256).map(|id| id as Id).filter(|&id| {
+                body.concnt(id) == 0 && history.snapshots.iter().any(|s| s.chems[id as usize] > 0)
+            }).collect(),
+            since: history.snapshots.front().map_or(creature.lived(), |s| s.tick),
+            drives: DRIVES.iter().map(|&drive| {
+                let locus = drive.locus() as usize;
+                (drive, history.snapshots.iter().map(|s| s.loci[locus]).collect())
+            }).collect(),
+        }
+    }
+
+    pub fn report(&self, names: &Names) -> String {
+        let chemicals = |ids: &[Id]| {
+            ids.iter().map(|&id| names.chemical(id)).collect::<Vec<String>>().join(", ")
+        };
+        let mut out = format!("died at t = {} as {:?}: ", self.lived, self.age);
+        out.push_str(&match self.cause {
+            DeathCause::Receptor { gene, organ: None, chemical } => {
+                format!("receptor in gene {} sensing {}", gene, names.chemical(chemical))
+            },
+            DeathCause::Receptor { gene, organ: Some(organ), chemical } => {
+                format!("receptor in gene {} of organ {} sensing {}", gene, organ,
+                        names.chemical(chemical))
+            },
+            DeathCause::OrganFailure(organ) => format!("failure of vital organ {}", organ),
+            DeathCause::Starvation => "starvation".to_owned(),
+            DeathCause::OldAge => "old age".to_owned(),
+            DeathCause::Unknown => "unknown cause".to_owned(),
+        });
+        out.push_str(&format!("\nsaturated: {}\ndepleted: {}\n", chemicals(&self.saturated),
+                              chemicals(&self.depleted)));
+        for &(drive, ref levels) in self.drives.iter() {
+            let levels: Vec<String> = levels.iter().map(|l| l.to_string()).collect();
+            out.push_str(&format!("{:?} since t = {}: {}\n", drive, self.since,
+                                  levels.join(" ")));
+        }
+        out
+    }
+}
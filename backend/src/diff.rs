@@ -0,0 +1,232 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result};
+use std::io::prelude::*;
+use std::path::Path;
+use genome::{Gene, Genome};
+use rustc_serialize::json::{decode, encode};
+
+/// Genes of the same kind closer than this are treated as one gene that was modified rather than
+/// one removed and another added.
+const MODIFIED_THRESHOLD: f32 = 0.5;
+
+#[derive(Clone, RustcEncodable, RustcDecodable)]
+pub enum GeneChange {
+    /// A gene present only in the new genome, with its index there.
+    Added(usize, Gene),
+    /// A gene present only in the old genome, with its index there.
+    Removed(usize, Gene),
+    /// A gene that changed, with its index in the old genome and its old and new forms.
+    Modified(usize, Gene, Gene),
+}
+
+impl GeneChange {
+    /// The fields that differ in a modification, as (field, old, new).
+    pub fn field_changes(&self) -> Vec<(&'static str, String, String)> {
+        match *self {
+            GeneChange::Modified(_, ref before, ref after) => {
+                before.fields().into_iter().zip(after.fields()).filter(|&((_, ref a), (_, ref b))| {
+                    a != b
+                }).map(|((name, a), (_, b))| (name, a, b)).collect()
+            },
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, RustcEncodable, RustcDecodable)]
+pub struct GenomeDiff {
+    pub changes: Vec<GeneChange>,
+}
+
+#[derive(Debug)]
+pub struct Conflict {
+    /// Index of the change that could not be applied.
+    pub change: usize,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "change {} does not match any gene in the target genome", self.change)
+    }
+}
+
+impl GenomeDiff {
+    /// Aligns the genes of two genomes and describes how to get from `old` to `new`. Identical
+    /// genes are paired first, then remaining genes of the same kind are paired by distance.
+    pub fn between(old: &Genome, new: &Genome) -> GenomeDiff {
+        let (old, new) = (old.genes(), new.genes());
+        let mut pairs: Vec<Option<usize>> = vec![None; old.len()];
+        let mut taken = vec![false; new.len()];
+        for (n, gene) in old.iter().enumerate() {
+            if let Some(m) = (0 .. new.len()).find(|&m| {
+                !taken[m] && gene.distance(&new[m]) == Some(0.0)
+            }) {
+                pairs[n] = Some(m);
+                taken[m] = true;
+            }
+        }
+        let mut changes = Vec::new();
+        for (n, gene) in old.iter().enumerate() {
+            if pairs[n].is_some() { continue }
+            let closest = (0 .. new.len()).filter(|&m| !taken[m])
+                .filter_map(|m| gene.distance(&new[m]).map(|d| (m, d)))
+                .filter(|&(_, d)| d < MODIFIED_THRESHOLD)
+                .fold(None, |best: Option<(usize, f32)>, (m, d)| match best {
+                    Some((_, bd)) if bd <= d => best,
+                    _ => Some((m, d)),
+                });
+            match closest {
+                Some((m, _)) => {
+                    taken[m] = true;
+                    changes.push(GeneChange::Modified(n, gene.clone(), new[m].clone()));
+                },
+                None => changes.push(GeneChange::Removed(n, gene.clone())),
+            }
+        }
+        for (m, gene) in new.iter().enumerate() {
+            if !taken[m] {
+                changes.push(GeneChange::Added(m, gene.clone()));
+            }
+        }
+        GenomeDiff { changes: changes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Applies this diff to another genome, producing a child of it. Removed and modified genes
+    /// are located by content rather than index, so the target need not be the diff's old genome;
+    /// only the fields that changed are carried over to the matched gene. Added genes go in at
+    /// their index in the new genome, or at the end if the target is too short.
+    pub fn apply(&self, target: &Genome) -> ::std::result::Result<Genome, Conflict> {
+        let mut patched = target.offspring();
+        for (n, change) in self.changes.iter().enumerate() {
+            match *change {
+                GeneChange::Added(index, ref gene) => {
+                    let index = index.min(patched.genes().len());
+                    patched.insert_gene_at(index, gene.clone());
+                },
+                GeneChange::Removed(_, ref gene) => {
+                    let index = try!(locate(&patched, gene).ok_or(Conflict { change: n }));
+                    patched.remove_gene(index);
+                },
+                GeneChange::Modified(_, ref before, ref after) => {
+                    let index = try!(locate(&patched, before).ok_or(Conflict { change: n }));
+                    let mut gene = patched.genes()[index].clone();
+                    for (field, _, _) in change.field_changes() {
+                        gene.copy_field(after, field);
+                    }
+                    patched.replace_gene(index, gene);
+                },
+            }
+        }
+        Ok(patched)
+    }
+
+    pub fn load<T: AsRef<Path>>(path: T) -> Result<GenomeDiff> {
+        let mut f = try!(File::open(path.as_ref()));
+        let mut data = String::new();
+        try!(f.read_to_string(&mut data));
+        decode(&data).map_err(|_|
+            Error::new(ErrorKind::InvalidInput, "Failed to decode genome diff.")
+        )
+    }
+
+    pub fn save<T: AsRef<Path>>(&self, path: T) -> Result<()> {
+        let mut f = try!(File::create(path.as_ref()));
+        try!(f.write_all(try!(encode(self).map_err(|_|
+            Error::new(ErrorKind::InvalidInput, "Failed to encode genome diff.")
+        )).as_bytes()));
+        f.flush()
+    }
+}
+
+/// Finds the gene in `genome` that is identical to `gene`, or failing that the closest gene of the
+/// same kind.
+fn locate(genome: &Genome, gene: &Gene) -> Option<usize> {
+    genome.genes().iter().enumerate()
+        .filter_map(|(n, g)| gene.distance(g).map(|d| (n, d)))
+        .filter(|&(_, d)| d < MODIFIED_THRESHOLD)
+        .fold(None, |best: Option<(usize, f32)>, (n, d)| match best {
+            Some((_, bd)) if bd <= d => best,
+            _ => Some((n, d)),
+        }).map(|(n, _)| n)
+}
+
+impl fmt::Display for GenomeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in self.changes.iter() {
+            match *change {
+                GeneChange::Added(n, ref gene) => try!(writeln!(f, "+ [{}] {}", n, describe(gene))),
+                GeneChange::Removed(n, ref gene) => {
+                    try!(writeln!(f, "- [{}] {}", n, describe(gene)))
+                },
+                GeneChange::Modified(n, ref gene, _) => {
+                    try!(writeln!(f, "~ [{}] {}", n, gene.name()));
+                    for (field, old, new) in change.field_changes() {
+                        try!(writeln!(f, "      {}: {} -> {}", field, old, new));
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+fn describe(gene: &Gene) -> String {
+    let fields: Vec<String> = gene.fields().into_iter().map(|(name, value)| {
+        format!("{}: {}", name, value)
+    }).collect();
+    format!("{} {{ {} }}", gene.name(), fields.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use dsl::{parse_genome, print_genome, Names};
+    use super::*;
+
+    fn genome(text: &str) -> Genome {
+        parse_genome(text).unwrap().0
+    }
+
+    fn round_trip(old: &str, new: &str) {
+        let (old, new) = (genome(old), genome(new));
+        let diff = GenomeDiff::between(&old, &new);
+        assert!(!diff.is_empty());
+        let patched = diff.apply(&old).unwrap();
+        assert_eq!(print_genome(&patched, &Names::new()), print_genome(&new, &Names::new()));
+    }
+
+    #[test]
+    fn identical_genomes_have_no_changes() {
+        let text = "initial 1 10\nreaction normal 1*1 + 1*2 -> 1*3 + 1*4 every 2";
+        assert!(GenomeDiff::between(&genome(text), &genome(text)).is_empty());
+    }
+
+    #[test]
+    fn patch_carries_a_changed_field() {
+        round_trip("initial 1 10\nreaction normal 1*1 + 1*2 -> 1*3 + 1*4 every 2",
+                   "initial 1 10\nreaction normal 1*1 + 1*2 -> 1*3 + 1*4 every 5");
+    }
+
+    #[test]
+    fn patch_keeps_genes_in_order() {
+        round_trip("initial 1 10\ninitial 2 10\ninitial 3 10", "initial 2 10\ninitial 3 10");
+        round_trip("initial 1 10\ninitial 2 10\ninitial 3 10",
+                   "initial 2 10\nreaction decay 1*9 -> nothing\ninitial 3 10");
+    }
+
+    #[test]
+    fn patch_carries_a_changed_reaction_kind() {
+        let (old, new) = ("reaction fusion 1*1 + 1*2 -> 1*3",
+                          "reaction catalytic 1*1 + 1*2 -> 1*1 + 1*3");
+        let diff = GenomeDiff::between(&genome(old), &genome(new));
+        assert!(diff.changes.iter().all(|c| match *c {
+            GeneChange::Modified(..) => false,
+            _ => true,
+        }));
+        round_trip(old, new);
+    }
+}
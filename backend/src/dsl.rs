@@ -0,0 +1,537 @@
+//! A line-oriented text format for genomes, for people rather than machines.
+//!
+//! ```text
+//! # Comments run to the end of the line.
+//! chemical 12 glucose
+//! chemical 13 oxygen
+//! chemical 14 energy
+//! chemical 15 co2
+//! locus 40 fear
+//! initial glucose 100
+//! emitter digital locus hunger >= 100 -> +20 chem 12 every 4 clear invert
+//! reaction normal 2*glucose + 1*oxygen -> 1*energy + 1*co2 every 4
+//! reaction decay 1*co2 -> nothing every 8 mass-action 0.05
+//! receptor analogue chem glucose > 10 -> locus hunger nominal 0 gain 255 invert
+//! brain 2 1 0.5 -0.25 1
+//! organ vital toxin co2 repair glucose import glucose 5 export energy 2
+//!     reaction fusion 1*glucose + 1*glucose -> 1*energy
+//! end
+//! clock 50 50 50 100 200 100
+//! female receptor digital chem glucose > 200 -> locus fertility nominal 0 gain 255
+//! ```
+//!
+//! Chemicals and loci may be referred to by number or by a declared name; the built-in loci are
+//! always available by name. Each chemical and locus can be named once, and no two can share a
+//! name. Reaction kinds are `normal`, `fusion`, `decay`, `catalytic` and `breakdown`; a reaction
+//! fires all-or-nothing unless it is given a `mass-action` rate constant. The genes between
+//! `organ` and `end` belong to the organ, and organs cannot be nested. A clock gives the ticks
+//! spent as a baby, child, adolescent, youth, adult and old creature. A gene prefixed with `male`
+//! or `female` is only expressed in creatures of that sex.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::io::prelude::*;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::FromStr;
+use std::vec::IntoIter;
+use chem::{Chemical, Coefficient, Emitter, Id, IoType, Kinetics, Reaction, ReactionType,
+           Reagent, Receptor};
+use creature::{Locus, LocusId, Sex};
+use genome::{Gene, Genome};
+use life::LifeClock;
+use organ::{Direction, Organ, Transport};
+
+#[derive(Clone, Debug, Default)]
+pub struct Names {
+    chemicals: BTreeMap<Id, String>,
+    loci: BTreeMap<LocusId, String>,
+}
+
+impl Names {
+    pub fn new() -> Names {
+        Names::default()
+    }
+
+    /// Names a chemical, unless it already has a name or the name is taken.
+    pub fn name_chemical(&mut self, id: Id, name: &str) -> Result<(), String> {
+        if let Some(old) = self.chemicals.get(&id) {
+            return Err(format!("chemical {} is already named `{}`", id, old))
+        }
+        if let Some(other) = self.lookup_chemical(name) {
+            return Err(format!("`{}` already names chemical {}", name, other))
+        }
+        self.chemicals.insert(id, name.to_owned());
+        Ok(())
+    }
+
+    /// Names a locus, unless it is built in or already has a name, or the name is taken.
+    pub fn name_locus(&mut self, id: LocusId, name: &str) -> Result<(), String> {
+        if Locus::from_id(id).is_some() || self.loci.contains_key(&id) {
+            return Err(format!("locus {} is already named `{}`", id, self.locus(id)))
+        }
+        if let Some(other) = self.lookup_locus(name) {
+            return Err(format!("`{}` already names locus {}", name, other))
+        }
+        self.loci.insert(id, name.to_owned());
+        Ok(())
+    }
+
+    /// The declared name of a chemical, or its number.
+    pub fn chemical(&self, id: Id) -> String {
+        self.chemicals.get(&id).cloned().unwrap_or_else(|| id.to_string())
+    }
+
+    /// The declared or built-in name of a locus, or its number.
+    pub fn locus(&self, id: LocusId) -> String {
+        self.loci.get(&id).cloned().or_else(|| Locus::from_id(id).map(|l| l.name().to_owned()))
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    pub fn lookup_chemical(&self, name: &str) -> Option<Id> {
+        name.parse().ok().or_else(|| {
+            self.chemicals.iter().find(|&(_, n)| n == name).map(|(&id, _)| id)
+        })
+    }
+
+    pub fn lookup_locus(&self, name: &str) -> Option<LocusId> {
+        name.parse().ok().or_else(|| {
+            self.loci.iter().find(|&(_, n)| n == name).map(|(&id, _)| id)
+        }).or_else(|| (0 .. 256).map(|id| id as LocusId).find(|&id| {
+            Locus::from_id(id).map_or(false, |l| l.name() == name)
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+type Tokens = Peekable<IntoIter<String>>;
+
+/// Parses a genome written in the text format, returning it along with the names it declared.
+pub fn parse_genome(text: &str) -> Result<(Genome, Names), ParseError> {
+    let mut names = Names::new();
+    let mut genes = Vec::new();
+    // The organ currently being read, along with the line it started on.
+    let mut organ: Option<(usize, Organ)> = None;
+    for (n, line) in text.lines().enumerate() {
+        let error = |message: String| ParseError { line: n + 1, message: message };
+        let line = line.split('#').next().unwrap().replace("*", " * ");
+        let mut tokens = line.split_whitespace().map(|t| t.to_owned()).collect::<Vec<_>>()
+                             .into_iter().peekable();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let gene = if keyword == "end" {
+            match organ.take() {
+                Some((_, o)) => Some(Gene::Organ(o)),
+                None => return Err(error("`end` without `organ`".to_owned())),
+            }
+        } else {
+            try!(parse_line(&keyword, &mut tokens, &mut names).map_err(&error))
+        };
+        if let Some(extra) = tokens.next() {
+            return Err(error(format!("unexpected `{}`", extra)));
+        }
+        if keyword == "organ" {
+            if organ.is_some() { return Err(error("organs cannot be nested".to_owned())) }
+            if let Some(Gene::Organ(o)) = gene {
+                organ = Some((n + 1, o));
+            }
+        } else if let Some(gene) = gene {
+            match organ {
+                Some((_, ref mut o)) => o.genes.push(gene),
+                None => genes.push(gene),
+            }
+        }
+    }
+    if let Some((line, _)) = organ {
+        return Err(ParseError { line: line, message: "`organ` without `end`".to_owned() })
+    }
+    Ok((Genome::from_genes(genes), names))
+}
+
+/// Loads a genome from either a JSON file or, for any other extension, the text format.
+pub fn load_genome<T: AsRef<Path>>(path: T) -> io::Result<(Genome, Names)> {
+    if path.as_ref().extension().map_or(false, |ext| ext == "json") {
+        return Genome::load(path).map(|genome| (genome, Names::new()))
+    }
+    let mut f = try!(File::open(path.as_ref()));
+    let mut text = String::new();
+    try!(f.read_to_string(&mut text));
+    parse_genome(&text).map_err(|e| io::Error::new(ErrorKind::InvalidInput, e.to_string()))
+}
+
+fn parse_line(keyword: &str, tokens: &mut Tokens, names: &mut Names)
+              -> Result<Option<Gene>, String> {
+    Ok(Some(match keyword {
+        "chemical" => {
+            let id = try!(number(tokens));
+            try!(names.name_chemical(id, &try!(identifier(tokens))));
+            return Ok(None)
+        },
+        "locus" => {
+            let id = try!(number(tokens));
+            try!(names.name_locus(id, &try!(identifier(tokens))));
+            return Ok(None)
+        },
+        "initial" => {
+            let id = try!(chemical(tokens, names));
+            Gene::InitialState(Chemical::with_concentration(id, try!(number(tokens))))
+        },
+        "emitter" => {
+            let kind = try!(io_type(tokens));
+            try!(expect(tokens, "locus"));
+            let locus = try!(locus(tokens, names));
+            try!(expect(tokens, ">="));
+            let threshold = try!(number(tokens));
+            try!(expect(tokens, "->"));
+            let gain = try!(tokens.next().ok_or("expected a gain".to_owned()));
+            let gain = try!(gain.trim_start_matches('+').parse().map_err(|_| {
+                format!("expected a gain, found `{}`", gain)
+            }));
+            try!(expect(tokens, "chem"));
+            let chemical = try!(chemical(tokens, names));
+            let rate = try!(every(tokens));
+            let clear = flag(tokens, "clear");
+            let invert = flag(tokens, "invert");
+            Gene::Emitter(Emitter::new(kind, chemical, rate, gain, locus, threshold, clear, invert))
+        },
+        "reaction" => {
+            let kind = try!(tokens.next().ok_or("expected a reaction kind".to_owned()));
+            let reactants = try!(side(tokens, names));
+            try!(expect(tokens, "->"));
+            let products = try!(side(tokens, names));
+            let kind = try!(reaction_type(&kind, &reactants, &products));
+            let rate = try!(every(tokens));
+            let kinetics = if flag(tokens, "mass-action") {
+                Kinetics::MassAction(try!(number(tokens)))
+            } else {
+                Kinetics::AllOrNothing
+            };
+            Gene::Reaction(Reaction::with_kinetics(kind, rate, kinetics))
+        },
+        "receptor" => {
+            let kind = try!(io_type(tokens));
+            try!(expect(tokens, "chem"));
+            let chemical = try!(chemical(tokens, names));
+            try!(expect(tokens, ">"));
+            let threshold = try!(number(tokens));
+            try!(expect(tokens, "->"));
+            try!(expect(tokens, "locus"));
+            let locus = try!(locus(tokens, names));
+            try!(expect(tokens, "nominal"));
+            let nominal = try!(number(tokens));
+            try!(expect(tokens, "gain"));
+            let gain = try!(number(tokens));
+            let invert = flag(tokens, "invert");
+            Gene::Receptor(Receptor::new(kind, chemical, locus, nominal, gain, threshold, invert))
+        },
+        "brain" => {
+            let inputs = try!(number(tokens));
+            let outputs = try!(number(tokens));
+            let mut weights = Vec::new();
+            while tokens.peek().is_some() {
+                weights.push(try!(number(tokens)));
+            }
+            Gene::Brain(inputs, outputs, weights)
+        },
+        "organ" => {
+            let vital = flag(tokens, "vital");
+            try!(expect(tokens, "toxin"));
+            let toxin = try!(chemical(tokens, names));
+            try!(expect(tokens, "repair"));
+            let repair = try!(chemical(tokens, names));
+            let mut transport = Vec::new();
+            loop {
+                let direction = if flag(tokens, "import") {
+                    Direction::Import
+                } else if flag(tokens, "export") {
+                    Direction::Export
+                } else {
+                    break
+                };
+                let chemical = try!(chemical(tokens, names));
+                transport.push(Transport::new(chemical, try!(number(tokens)), direction));
+            }
+            Gene::Organ(Organ::new(Vec::new(), vital, toxin, repair, transport))
+        },
+        "clock" => {
+            let mut durations = [0; 6];
+            for duration in durations.iter_mut() {
+                *duration = try!(number(tokens));
+            }
+            Gene::Clock(LifeClock::new(durations))
+        },
+        "male" | "female" => {
+            let sex = if keyword == "male" { Sex::Male } else { Sex::Female };
+            let keyword = match tokens.next() {
+                Some(ref keyword) if keyword == "organ" => {
+                    return Err("organs cannot be sex-linked".to_owned())
+                },
+                Some(keyword) => keyword,
+                None => return Err(format!("expected a gene after `{}`", keyword)),
+            };
+            match try!(parse_line(&keyword, tokens, names)) {
+                Some(gene) => Gene::SexLinked(sex, Box::new(gene)),
+                None => return Err(format!("`{}` is not a gene", keyword)),
+            }
+        },
+        _ => return Err(format!("unknown gene `{}`", keyword))
+    }))
+}
+
+fn number<T: FromStr>(tokens: &mut Tokens) -> Result<T, String> {
+    match tokens.next() {
+        Some(token) => token.parse().map_err(|_| format!("expected a number, found `{}`", token)),
+        None => Err("expected a number".to_owned()),
+    }
+}
+
+fn identifier(tokens: &mut Tokens) -> Result<String, String> {
+    match tokens.next() {
+        Some(ref token) if is_identifier(token) => Ok(token.clone()),
+        Some(token) => Err(format!("expected a name, found `{}`", token)),
+        None => Err("expected a name".to_owned()),
+    }
+}
+
+fn is_identifier(token: &str) -> bool {
+    token.chars().next().map_or(false, |c| c.is_alphabetic() || c == '_') &&
+        token.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn chemical(tokens: &mut Tokens, names: &Names) -> Result<Id, String> {
+    let token = try!(tokens.next().ok_or("expected a chemical".to_owned()));
+    names.lookup_chemical(&token).ok_or(format!("unknown chemical `{}`", token))
+}
+
+fn locus(tokens: &mut Tokens, names: &Names) -> Result<LocusId, String> {
+    let token = try!(tokens.next().ok_or("expected a locus".to_owned()));
+    names.lookup_locus(&token).ok_or(format!("unknown locus `{}`", token))
+}
+
+fn expect(tokens: &mut Tokens, word: &str) -> Result<(), String> {
+    match tokens.next() {
+        Some(ref token) if token == word => Ok(()),
+        Some(token) => Err(format!("expected `{}`, found `{}`", word, token)),
+        None => Err(format!("expected `{}`", word)),
+    }
+}
+
+fn flag(tokens: &mut Tokens, word: &str) -> bool {
+    if tokens.peek().map_or(false, |t| t == word) {
+        tokens.next();
+        true
+    } else {
+        false
+    }
+}
+
+fn every(tokens: &mut Tokens) -> Result<u8, String> {
+    if flag(tokens, "every") { number(tokens) } else { Ok(0) }
+}
+
+fn io_type(tokens: &mut Tokens) -> Result<IoType, String> {
+    match tokens.next() {
+        Some(ref token) if token == "analogue" || token == "analog" => Ok(IoType::Analogue),
+        Some(ref token) if token == "digital" => Ok(IoType::Digital),
+        Some(token) => Err(format!("expected `analogue` or `digital`, found `{}`", token)),
+        None => Err("expected `analogue` or `digital`".to_owned()),
+    }
+}
+
+/// One side of a reaction: `nothing`, or terms like `2*glucose` joined by `+`.
+fn side(tokens: &mut Tokens, names: &Names) -> Result<Vec<Reagent>, String> {
+    if flag(tokens, "nothing") { return Ok(Vec::new()) }
+    let mut terms = Vec::new();
+    loop {
+        let coefficient = try!(Coefficient::new(try!(number(tokens))).ok_or(
+            "reaction coefficients must be non-zero".to_owned()
+        ));
+        try!(expect(tokens, "*"));
+        terms.push(Reagent::new(try!(chemical(tokens, names)), coefficient));
+        if !flag(tokens, "+") { return Ok(terms) }
+    }
+}
+
+fn reaction_type(kind: &str, reactants: &[Reagent], products: &[Reagent])
+                 -> Result<ReactionType, String> {
+    let same = |a: &Reagent, b: &Reagent| a.id == b.id && a.coefficient == b.coefficient;
+    match (kind, reactants, products) {
+        ("normal", &[a, b], &[c, d]) => Ok(ReactionType::Normal(a, b, c, d)),
+        ("fusion", &[a, b], &[c]) => Ok(ReactionType::Fusion(a, b, c)),
+        ("decay", &[a], &[]) => Ok(ReactionType::Decay(a)),
+        ("catalytic", &[a, b], &[a2, c]) if same(&a, &a2) => Ok(ReactionType::Catalytic(a, b, c)),
+        ("breakdown", &[a, b], &[a2]) if same(&a, &a2) => {
+            Ok(ReactionType::CatalyticBreakdown(a, b))
+        },
+        ("normal", _, _) => Err("a normal reaction has the form A + B -> C + D".to_owned()),
+        ("fusion", _, _) => Err("a fusion reaction has the form A + B -> C".to_owned()),
+        ("decay", _, _) => Err("a decay reaction has the form A -> nothing".to_owned()),
+        ("catalytic", _, _) => Err("a catalytic reaction has the form A + B -> A + C".to_owned()),
+        ("breakdown", _, _) => Err("a breakdown reaction has the form A + B -> A".to_owned()),
+        _ => Err(format!("unknown reaction kind `{}`", kind)),
+    }
+}
+
+/// Writes a genome in the text format, using and declaring any names given.
+pub fn print_genome(genome: &Genome, names: &Names) -> String {
+    let mut out = String::new();
+    for (id, name) in names.chemicals.iter() {
+        out.push_str(&format!("chemical {} {}\n", id, name));
+    }
+    for (id, name) in names.loci.iter() {
+        out.push_str(&format!("locus {} {}\n", id, name));
+    }
+    for gene in genome.genes().iter() {
+        out.push_str(&print_gene(gene, names));
+        out.push('\n');
+    }
+    out
+}
+
+pub fn print_gene(gene: &Gene, names: &Names) -> String {
+    let io = |kind: IoType| match kind {
+        IoType::Analogue => "analogue",
+        IoType::Digital => "digital",
+    };
+    let every = |rate: u8| if rate == 0 { String::new() } else { format!(" every {}", rate) };
+    let flag = |set: bool, word: &str| if set { format!(" {}", word) } else { String::new() };
+    let terms = |reagents: &[Reagent]| if reagents.is_empty() {
+        "nothing".to_owned()
+    } else {
+        reagents.iter().map(|r| format!("{}*{}", r.coefficient.get(), names.chemical(r.id)))
+             .collect::<Vec<_>>().join(" + ")
+    };
+    match *gene {
+        Gene::InitialState(ref c) => format!("initial {} {}", names.chemical(c.id()), c.concnt()),
+        Gene::Emitter(ref e) => format!(
+            "emitter {} locus {} >= {} -> +{} chem {}{}{}{}", io(e.kind), names.locus(e.locus),
+            e.threshold, e.gain, names.chemical(e.chemical), every(e.rate),
+            flag(e.clear_after_read, "clear"), flag(e.invert, "invert")
+        ),
+        Gene::Reaction(ref r) => {
+            let kind = match r.kind {
+                ReactionType::Normal(..) => "normal",
+                ReactionType::Fusion(..) => "fusion",
+                ReactionType::Decay(..) => "decay",
+                ReactionType::Catalytic(..) => "catalytic",
+                ReactionType::CatalyticBreakdown(..) => "breakdown",
+            };
+            let kinetics = match r.kinetics {
+                Kinetics::AllOrNothing => String::new(),
+                Kinetics::MassAction(k) => format!(" mass-action {}", k),
+            };
+            format!("reaction {} {} -> {}{}{}", kind, terms(&r.kind.reactants()),
+                    terms(&r.kind.products()), every(r.rate), kinetics)
+        },
+        Gene::Receptor(ref r) => format!(
+            "receptor {} chem {} > {} -> locus {} nominal {} gain {}{}", io(r.kind),
+            names.chemical(r.chemical), r.threshold, names.locus(r.locus), r.nominal, r.gain,
+            flag(r.invert, "invert")
+        ),
+        Gene::Brain(inputs, outputs, ref weights) => {
+            let weights: Vec<String> = weights.iter().map(|w| w.to_string()).collect();
+            format!("brain {} {} {}", inputs, outputs, weights.join(" ")).trim_end().to_owned()
+        },
+        Gene::Organ(ref o) => {
+            let mut out = format!("organ{} toxin {} repair {}", flag(o.vital, "vital"),
+                                  names.chemical(o.toxin), names.chemical(o.repair));
+            for t in o.transport.iter() {
+                out.push_str(&format!(" {} {} {}", match t.direction {
+                    Direction::Import => "import",
+                    Direction::Export => "export",
+                }, names.chemical(t.chemical), t.rate));
+            }
+            for gene in o.genes.iter() {
+                for line in print_gene(gene, names).lines() {
+                    out.push_str(&format!("\n    {}", line));
+                }
+            }
+            out.push_str("\nend");
+            out
+        },
+        Gene::Clock(ref c) => {
+            let durations: Vec<String> = c.durations.iter().map(|d| d.to_string()).collect();
+            format!("clock {}", durations.join(" "))
+        },
+        Gene::SexLinked(sex, ref g) => format!("{} {}", match sex {
+            Sex::Male => "male",
+            Sex::Female => "female",
+        }, print_gene(g, names)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &'static str = "chemical 12 glucose
+chemical 14 energy
+locus 40 fear
+initial glucose 100
+emitter digital locus fear >= 100 -> +20 chem glucose every 4 clear invert
+reaction normal 2*glucose + 1*13 -> 1*energy + 1*15 every 4 mass-action 0.5
+receptor analogue chem glucose > 10 -> locus hunger nominal 0 gain 255 invert
+organ vital toxin 15 repair glucose import glucose 5 export energy 2
+    reaction decay 1*15 -> nothing
+end
+female receptor digital chem glucose > 200 -> locus fertility nominal 0 gain 255
+";
+
+    fn error(text: &str) -> String {
+        parse_genome(text).err().expect("parsed").message
+    }
+
+    #[test]
+    fn printing_and_parsing_round_trips() {
+        let (genome, names) = parse_genome(TEXT).unwrap();
+        let printed = print_genome(&genome, &names);
+        assert_eq!(printed, TEXT);
+        let (again, names) = parse_genome(&printed).unwrap();
+        assert_eq!(print_genome(&again, &names), printed);
+    }
+
+    #[test]
+    fn names_resolve_to_numbers() {
+        let (genome, _) = parse_genome(TEXT).unwrap();
+        match genome.genes()[1] {
+            Gene::Emitter(ref e) => {
+                assert_eq!((e.locus, e.chemical, e.gain), (40, 12, 20));
+                assert!(e.clear_after_read && e.invert);
+            },
+            _ => panic!("expected an emitter"),
+        }
+        match genome.genes()[3] {
+            Gene::Receptor(ref r) => assert_eq!(r.locus, Locus::Hunger as LocusId),
+            _ => panic!("expected a receptor"),
+        }
+    }
+
+    #[test]
+    fn declarations_cannot_clash() {
+        assert_eq!(error("locus 40 hunger"), "`hunger` already names locus 7");
+        assert_eq!(error("locus 8 lust"), "locus 8 is already named `sex_drive`");
+        assert_eq!(error("chemical 1 food\nchemical 2 food"), "`food` already names chemical 1");
+        assert_eq!(error("chemical 1 food\nchemical 1 fuel"), "chemical 1 is already named `food`");
+    }
+
+    #[test]
+    fn unknown_names_are_reported_with_their_line() {
+        let error = parse_genome("chemical 1 food\n\ninitial fod 10").err().unwrap();
+        assert_eq!((error.line, &error.message[..]), (3, "unknown chemical `fod`"));
+    }
+}
@@ -0,0 +1,155 @@
+use std::mem;
+use chem::Id;
+use creature::{Age, Creature, Drive, LocusValue};
+use death::DeathCause;
+use population::CreatureId;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Event {
+    StageChanged { from: Age, to: Age },
+    Died(DeathCause),
+    /// A drive went past a watched threshold, upwards if `rising`.
+    DriveCrossed { drive: Drive, threshold: LocusValue, rising: bool },
+    /// A chemical reached 255 in the bloodstream or, if `organ` is set, in that organ. Only
+    /// reported once asked for with `EventBus::watch_saturation`.
+    ChemicalSaturated { chemical: Id, organ: Option<usize> },
+    /// A gene started acting after not having done so: an emitter's locus reached its threshold,
+    /// a reaction found enough reactants or a receptor's chemical went over its threshold. Genes
+    /// inside organs are numbered within their organ.
+    GeneSwitchedOn { gene: usize, organ: Option<usize> },
+    /// The creature became pregnant by mating with `father`.
+    Conceived { father: CreatureId },
+    GaveBirth { child: CreatureId },
+}
+
+/// Something told about every event as it happens, see `EventBus::subscribe`.
+pub trait Subscriber: Send + Sync {
+    fn notify(&mut self, tick: u64, event: &Event);
+}
+
+impl<F: FnMut(u64, &Event) + Send + Sync> Subscriber for F {
+    fn notify(&mut self, tick: u64, event: &Event) {
+        self(tick, event)
+    }
+}
+
+/// Passes a creature's events to its subscribers as they happen, and keeps them, stamped with the
+/// tick they happened on, until they are drained.
+pub struct EventBus {
+    subscribers: Vec<Box<dyn Subscriber>>,
+    pending: Vec<(u64, Event)>,
+    drives: Vec<(Drive, LocusValue)>,
+    saturation: bool,
+    /// How the creature was at the end of its last tick.
+    last: Option<Vitals>,
+}
+
+impl EventBus {
+    pub fn new() -> EventBus {
+        EventBus {
+            subscribers: Vec::new(), pending: Vec::new(), drives: Vec::new(), saturation: false,
+            last: None
+        }
+    }
+
+    pub fn subscribe<S: Subscriber + 'static>(&mut self, subscriber: S) {
+        self.subscribers.push(Box::new(subscriber));
+    }
+
+    /// Reports `DriveCrossed` whenever the drive goes past `threshold` in either direction.
+    pub fn watch_drive(&mut self, drive: Drive, threshold: LocusValue) {
+        self.drives.push((drive, threshold));
+    }
+
+    /// Reports `ChemicalSaturated` whenever a chemical reaches 255. This means looking at every
+    /// chemical in every compartment each tick, so it is off unless asked for.
+    pub fn watch_saturation(&mut self) {
+        self.saturation = true;
+    }
+
+    pub fn emit(&mut self, tick: u64, event: Event) {
+        for subscriber in self.subscribers.iter_mut() {
+            subscriber.notify(tick, &event);
+        }
+        self.pending.push((tick, event));
+    }
+
+    /// Events that have not been drained yet, oldest first.
+    pub fn pending(&self) -> &[(u64, Event)] {
+        &self.pending
+    }
+
+    pub fn drain(&mut self) -> Vec<(u64, Event)> {
+        mem::replace(&mut self.pending, Vec::new())
+    }
+}
+
+/// The parts of a creature that events are detected in, compared from one tick to the next so
+/// that changes made between ticks, such as injections, are noticed too.
+pub struct Vitals {
+    age: Age,
+    alive: bool,
+    drives: Vec<LocusValue>,
+    /// Saturated chemicals in the bloodstream followed by each organ, if saturation is watched.
+    saturated: Vec<Vec<Id>>,
+}
+
+impl Vitals {
+    /// The vitals at the end of the creature's last tick, or as they are now before its first.
+    pub fn last(creature: &mut Creature) -> Vitals {
+        let last = creature.events_mut().last.take();
+        last.unwrap_or_else(|| Vitals::of(creature))
+    }
+
+    pub fn of(creature: &Creature) -> Vitals {
+        let compartments = Some(None).into_iter().chain((0 .. creature.organs()).map(Some));
+        Vitals {
+            age: creature.age(),
+            alive: creature.is_alive(),
+            drives: creature.events().drives.iter().map(|&(drive, _)| {
+                creature.get_drive(drive)
+            }).collect(),
+            saturated: if creature.events().saturation {
+                compartments.map(|organ| saturated(creature, organ)).collect()
+            } else {
+                Vec::new()
+            },
+        }
+    }
+
+    /// Emits an event for everything that has changed in the creature since these were taken, and
+    /// keeps its vitals now for the next tick.
+    pub fn report(&self, creature: &mut Creature) {
+        let mut events = Vec::new();
+        let age = creature.age();
+        if age != self.age {
+            events.push(Event::StageChanged { from: self.age, to: age });
+        }
+        if self.alive && !creature.is_alive() {
+            events.push(Event::Died(creature.cause_of_death().unwrap_or(DeathCause::Unknown)));
+        }
+        for (&(drive, threshold), &old) in creature.events().drives.iter().zip(self.drives.iter()) {
+            let new = creature.get_drive(drive);
+            if (old < threshold) != (new < threshold) {
+                events.push(Event::DriveCrossed {
+                    drive: drive, threshold: threshold, rising: new > old
+                });
+            }
+        }
+        for (n, old) in self.saturated.iter().enumerate() {
+            let organ = if n == 0 { None } else { Some(n - 1) };
+            for chemical in saturated(creature, organ).into_iter().filter(|c| !old.contains(c)) {
+                events.push(Event::ChemicalSaturated { chemical: chemical, organ: organ });
+            }
+        }
+        for event in events {
+            creature.emit(event);
+        }
+        creature.events_mut().last = Some(Vitals::of(creature));
+    }
+}
+
+fn saturated(creature: &Creature, organ: Option<usize>) -> Vec<Id> {
+    let body = creature.compartment(organ);
+    (0 .. 256).map(|id| id as Id).filter(|&id| body.concnt(id) == 255).collect()
+}
@@ -2,10 +2,20 @@ use std::fs::File;
 use std::io::{Error, ErrorKind, Result};
 use std::io::prelude::*;
 use std::path::Path;
-use chem::{Chemical, Emitter, Reaction, Receptor};
-use creature::Creature;
+use chem::{Chemical, Emitter, Id, IoType, Kinetics, Reaction, Receptor};
+use creature::{Age, Body, Creature, DoubleBuffer, GeneState, Sex};
+use death::DeathCause;
+use events::{Event, Vitals};
+use life::LifeClock;
+use lineage::{Lineage, Mutation};
+use organ::{Direction, Organ};
+use recorder::Observer;
 use rand::{thread_rng, Rand, Rng};
+use rustc_serialize::{Decodable, Decoder};
 use rustc_serialize::json::{decode, encode};
+use validate::Validation;
+
+const MUTATION_ATTEMPTS: usize = 8;
 
 #[derive(Clone, RustcEncodable, RustcDecodable)]
 pub enum Gene {
@@ -14,6 +24,12 @@ pub enum Gene {
     Reaction(Reaction),
     Receptor(Receptor),
     Brain(usize, usize, Vec<f32>),
+    Organ(Organ),
+    /// Sets how long each stage of life lasts. Only the first clock in a genome counts, and a
+    /// genome without one ages by `LifeClock::default()`.
+    Clock(LifeClock),
+    /// A gene only expressed in creatures of one sex.
+    SexLinked(Sex, Box<Gene>),
 }
 
 impl Rand for Gene {
@@ -27,70 +43,523 @@ impl Rand for Gene {
     }
 }
 
-#[derive(Clone, RustcEncodable, RustcDecodable)]
+impl Gene {
+    /// How different two genes are, from `0.0` (identical) to `1.0`, or `None` if they are not
+    /// the same kind of gene. Identities (chemicals, loci, variants) weigh more than magnitudes.
+    pub fn distance(&self, other: &Gene) -> Option<f32> {
+        let ident = |a: u8, b: u8| if a == b { 0.0 } else { 1.0 };
+        let magnitude = |a: u8, b: u8| (a as f32 - b as f32).abs() / 255.0;
+        let flag = |a: bool, b: bool| if a == b { 0.0 } else { 1.0 };
+        let io = |a: IoType, b: IoType| ident(a as u8, b as u8);
+        Some(match (self, other) {
+            (&Gene::InitialState(ref a), &Gene::InitialState(ref b)) => weighted(&[
+                (2.0, ident(a.id(), b.id())), (1.0, magnitude(a.concnt(), b.concnt())),
+            ]),
+            (&Gene::Emitter(ref a), &Gene::Emitter(ref b)) => weighted(&[
+                (1.0, io(a.kind, b.kind)), (2.0, ident(a.chemical, b.chemical)),
+                (0.5, magnitude(a.rate, b.rate)), (1.0, magnitude(a.gain, b.gain)),
+                (2.0, ident(a.locus, b.locus)), (1.0, magnitude(a.threshold, b.threshold)),
+                (0.5, flag(a.clear_after_read, b.clear_after_read)),
+                (0.5, flag(a.invert, b.invert)),
+            ]),
+            (&Gene::Reaction(ref a), &Gene::Reaction(ref b)) => {
+                // Reactions of different kinds behave differently even with the same reagents.
+                if a.kind.name() != b.kind.name() { return Some(1.0) }
+                let (xs, ys) = (a.kind.reagents(), b.kind.reagents());
+                let kinetics = match (a.kinetics, b.kinetics) {
+                    (Kinetics::AllOrNothing, Kinetics::AllOrNothing) => 0.0,
+                    (Kinetics::MassAction(x), Kinetics::MassAction(y)) => (x - y).abs().min(1.0),
+                    _ => 1.0,
+                };
+                let mut terms = vec![(0.5, magnitude(a.rate, b.rate)), (1.0, kinetics)];
+                for (x, y) in xs.iter().zip(ys.iter()) {
+                    terms.push((2.0, ident(x.id, y.id)));
+                    terms.push((1.0, magnitude(x.coefficient.get(), y.coefficient.get())));
+                }
+                weighted(&terms)
+            },
+            (&Gene::Receptor(ref a), &Gene::Receptor(ref b)) => weighted(&[
+                (1.0, io(a.kind, b.kind)), (2.0, ident(a.chemical, b.chemical)),
+                (2.0, ident(a.locus, b.locus)), (1.0, magnitude(a.nominal, b.nominal)),
+                (1.0, magnitude(a.gain, b.gain)), (1.0, magnitude(a.threshold, b.threshold)),
+                (0.5, flag(a.invert, b.invert)),
+            ]),
+            (&Gene::Brain(ai, ao, ref aw), &Gene::Brain(bi, bo, ref bw)) => {
+                if ai != bi || ao != bo || aw.len() != bw.len() { return Some(1.0) }
+                let total = aw.iter().zip(bw.iter()).fold(0.0, |acc, (x, y)| acc + (x - y).abs());
+                (total / aw.len().max(1) as f32).min(1.0)
+            },
+            (&Gene::Organ(ref a), &Gene::Organ(ref b)) => {
+                let transport = if a.transport == b.transport { 0.0 } else { 1.0 };
+                weighted(&[
+                    (1.0, flag(a.vital, b.vital)), (1.0, ident(a.toxin, b.toxin)),
+                    (1.0, ident(a.repair, b.repair)), (1.0, transport),
+                    (2.0, set_distance(&a.genes, &b.genes)),
+                ])
+            },
+            (&Gene::Clock(ref a), &Gene::Clock(ref b)) => {
+                let terms: Vec<(f32, f32)> = a.durations.iter().zip(b.durations.iter())
+                    .map(|(&x, &y)| {
+                        (1.0, (x as f32 - y as f32).abs() / x.max(y).max(1) as f32)
+                    }).collect();
+                weighted(&terms)
+            },
+            (&Gene::SexLinked(a, ref ag), &Gene::SexLinked(b, ref bg)) => match ag.distance(bg) {
+                Some(d) => weighted(&[(1.0, ident(a as u8, b as u8)), (3.0, d)]),
+                None => 1.0,
+            },
+            _ => return None
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Gene::InitialState(_) => "InitialState",
+            Gene::Emitter(_) => "Emitter",
+            Gene::Reaction(_) => "Reaction",
+            Gene::Receptor(_) => "Receptor",
+            Gene::Brain(..) => "Brain",
+            Gene::Organ(_) => "Organ",
+            Gene::Clock(_) => "Clock",
+            Gene::SexLinked(..) => "SexLinked",
+        }
+    }
+
+    /// The gene's heritable parameters as printable name/value pairs.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        match *self {
+            Gene::InitialState(ref c) => vec![
+                ("chemical", c.id().to_string()), ("concentration", c.concnt().to_string()),
+            ],
+            Gene::Emitter(ref e) => vec![
+                ("kind", format!("{:?}", e.kind)), ("chemical", e.chemical.to_string()),
+                ("rate", e.rate.to_string()), ("gain", e.gain.to_string()),
+                ("locus", e.locus.to_string()), ("threshold", e.threshold.to_string()),
+                ("clear_after_read", e.clear_after_read.to_string()),
+                ("invert", e.invert.to_string()),
+            ],
+            Gene::Reaction(ref r) => {
+                let chems: Vec<String> = r.kind.reagents().iter().map(|r| {
+                    format!("{}*{}", r.coefficient.get(), r.id)
+                }).collect();
+                vec![
+                    ("kind", format!("{}({})", r.kind.name(), chems.join(", "))),
+                    ("rate", r.rate.to_string()),
+                    ("kinetics", format!("{:?}", r.kinetics)),
+                ]
+            },
+            Gene::Receptor(ref r) => vec![
+                ("kind", format!("{:?}", r.kind)), ("chemical", r.chemical.to_string()),
+                ("locus", r.locus.to_string()), ("nominal", r.nominal.to_string()),
+                ("gain", r.gain.to_string()), ("threshold", r.threshold.to_string()),
+                ("invert", r.invert.to_string()),
+            ],
+            Gene::Brain(inputs, outputs, ref weights) => vec![
+                ("inputs", inputs.to_string()), ("outputs", outputs.to_string()),
+                ("weights", format!("{:?}", weights)),
+            ],
+            Gene::Organ(ref o) => {
+                let transport: Vec<String> = o.transport.iter().map(|t| {
+                    format!("{} {}*{}", match t.direction {
+                        Direction::Import => "import",
+                        Direction::Export => "export",
+                    }, t.rate, t.chemical)
+                }).collect();
+                let genes: Vec<String> = o.genes.iter().map(|g| {
+                    let fields: Vec<String> = g.fields().into_iter().map(|(name, value)| {
+                        format!("{}: {}", name, value)
+                    }).collect();
+                    format!("{}({})", g.name(), fields.join(", "))
+                }).collect();
+                vec![
+                    ("vital", o.vital.to_string()), ("toxin", o.toxin.to_string()),
+                    ("repair", o.repair.to_string()), ("transport", transport.join(", ")),
+                    ("genes", genes.join("; ")),
+                ]
+            },
+            Gene::Clock(ref c) => vec![("durations", format!("{:?}", c.durations))],
+            Gene::SexLinked(sex, ref g) => {
+                let fields: Vec<String> = g.fields().into_iter().map(|(name, value)| {
+                    format!("{}: {}", name, value)
+                }).collect();
+                vec![
+                    ("sex", format!("{:?}", sex)),
+                    ("gene", format!("{}({})", g.name(), fields.join(", "))),
+                ]
+            },
+        }
+    }
+
+    /// Copies a single named field from another gene of the same kind. Returns false if the
+    /// genes differ in kind or the field does not exist.
+    pub fn copy_field(&mut self, from: &Gene, field: &str) -> bool {
+        match (self, from) {
+            (&mut Gene::InitialState(ref mut a), &Gene::InitialState(ref b)) => match field {
+                "chemical" => *a = Chemical::with_concentration(b.id(), a.concnt()),
+                "concentration" => *a = Chemical::with_concentration(a.id(), b.concnt()),
+                _ => return false,
+            },
+            (&mut Gene::Emitter(ref mut a), &Gene::Emitter(ref b)) => match field {
+                "kind" => a.kind = b.kind,
+                "chemical" => a.chemical = b.chemical,
+                "rate" => a.rate = b.rate,
+                "gain" => a.gain = b.gain,
+                "locus" => a.locus = b.locus,
+                "threshold" => a.threshold = b.threshold,
+                "clear_after_read" => a.clear_after_read = b.clear_after_read,
+                "invert" => a.invert = b.invert,
+                _ => return false,
+            },
+            (&mut Gene::Reaction(ref mut a), &Gene::Reaction(ref b)) => match field {
+                "kind" => a.kind = b.kind,
+                "rate" => a.rate = b.rate,
+                "kinetics" => a.kinetics = b.kinetics,
+                _ => return false,
+            },
+            (&mut Gene::Receptor(ref mut a), &Gene::Receptor(ref b)) => match field {
+                "kind" => a.kind = b.kind,
+                "chemical" => a.chemical = b.chemical,
+                "locus" => a.locus = b.locus,
+                "nominal" => a.nominal = b.nominal,
+                "gain" => a.gain = b.gain,
+                "threshold" => a.threshold = b.threshold,
+                "invert" => a.invert = b.invert,
+                _ => return false,
+            },
+            (&mut Gene::Brain(ref mut i, ref mut o, ref mut w), &Gene::Brain(bi, bo, ref bw)) => {
+                match field {
+                    "inputs" => *i = bi,
+                    "outputs" => *o = bo,
+                    "weights" => *w = bw.clone(),
+                    _ => return false,
+                }
+            },
+            (&mut Gene::Organ(ref mut a), &Gene::Organ(ref b)) => match field {
+                "vital" => a.vital = b.vital,
+                "toxin" => a.toxin = b.toxin,
+                "repair" => a.repair = b.repair,
+                "transport" => a.transport = b.transport.clone(),
+                "genes" => a.genes = b.genes.clone(),
+                _ => return false,
+            },
+            (&mut Gene::Clock(ref mut a), &Gene::Clock(ref b)) => match field {
+                "durations" => a.durations = b.durations,
+                _ => return false,
+            },
+            (&mut Gene::SexLinked(ref mut a, ref mut ag), &Gene::SexLinked(b, ref bg)) => {
+                match field {
+                    "sex" => *a = b,
+                    "gene" => *ag = bg.clone(),
+                    _ => return false,
+                }
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    /// A copy of the gene with one of its fields changed at random.
+    pub fn mutated<R: Rng>(&self, rng: &mut R) -> Gene {
+        match *self {
+            Gene::InitialState(ref ch) => if rng.gen() {
+                Gene::InitialState(Chemical::with_concentration(ch.id(), rng.gen()))
+            } else {
+                Gene::InitialState(Chemical::with_concentration(rng.gen(), ch.concnt()))
+            },
+            Gene::Emitter(ref e) => Gene::Emitter(match rng.gen_range(0, 8) {
+                1 => Emitter { kind: rng.gen(), .. e.clone() },
+                2 => Emitter { chemical: rng.gen(), .. e.clone() },
+                3 => Emitter { rate: rng.gen(), .. e.clone() },
+                4 => Emitter { gain: rng.gen(), .. e.clone() },
+                5 => Emitter { locus: rng.gen(), .. e.clone() },
+                6 => Emitter { threshold: rng.gen(), .. e.clone() },
+                7 => Emitter { clear_after_read: rng.gen(), .. e.clone() },
+                _ => Emitter { invert: rng.gen(), .. e.clone() },
+            }),
+            Gene::Reaction(ref r) => Gene::Reaction(match rng.gen_range(0, 3) {
+                1 => Reaction { kind: rng.gen(), .. r.clone() },
+                2 => Reaction { kinetics: rng.gen(), .. r.clone() },
+                _ => Reaction { rate: rng.gen(), .. r.clone() },
+            }),
+            Gene::Receptor(ref r) => Gene::Receptor(match rng.gen_range(0, 7) {
+                1 => Receptor { kind: rng.gen(), .. *r },
+                2 => Receptor { chemical: rng.gen(), .. *r },
+                3 => Receptor { locus: rng.gen(), .. *r },
+                4 => Receptor { nominal: rng.gen(), .. *r },
+                5 => Receptor { gain: rng.gen(), .. *r },
+                6 => Receptor { threshold: rng.gen(), .. *r },
+                _ => Receptor { invert: rng.gen(), .. *r },
+            }),
+            Gene::Organ(ref o) => Gene::Organ(match rng.gen_range(0, 5) {
+                1 => Organ { vital: rng.gen(), .. o.clone() },
+                2 => Organ { toxin: rng.gen(), .. o.clone() },
+                3 => Organ { repair: rng.gen(), .. o.clone() },
+                4 => {
+                    let mut o = o.clone();
+                    let n = rng.gen_range(0, o.transport.len() + 1);
+                    if n == o.transport.len() {
+                        o.transport.push(rng.gen());
+                    } else {
+                        o.transport.swap_remove(n);
+                    }
+                    o
+                },
+                _ => {
+                    let mut o = o.clone();
+                    let n = rng.gen_range(0, o.genes.len() + 1);
+                    if n == o.genes.len() {
+                        o.genes.push(rng.gen());
+                    } else {
+                        o.genes.swap_remove(n);
+                    }
+                    o
+                },
+            }),
+            Gene::Clock(ref c) => Gene::Clock(c.mutate(rng)),
+            Gene::SexLinked(sex, ref g) => if rng.gen_range(0, 4) == 0 {
+                Gene::SexLinked(if sex == Sex::Male { Sex::Female } else { Sex::Male }, g.clone())
+            } else {
+                Gene::SexLinked(sex, Box::new(g.mutated(rng)))
+            },
+            _ => panic!("Something went wrong: failed to mutate a gene.")
+        }
+    }
+
+    /// Expresses the gene once. Organs are stepped by their genome instead, since their
+    /// compartments are numbered by position in it, and so is the creature's life clock.
+    pub fn step(&self, state: &mut GeneState, body: &mut Body) {
+        match *self {
+            Gene::Emitter(ref e) => e.step(state, body),
+            Gene::Reaction(ref r) => r.step(state, body),
+            Gene::Receptor(ref r) => r.step(state, body),
+            Gene::SexLinked(sex, ref g) => if sex == body.sex {
+                g.step(state, body)
+            },
+            _ => ()
+        }
+    }
+
+    /// Every chemical the gene reads or writes, including those of the genes inside an organ.
+    pub fn chemicals(&self) -> Vec<Id> {
+        match *self {
+            Gene::InitialState(ref c) => vec![c.id()],
+            Gene::Emitter(ref e) => vec![e.chemical],
+            Gene::Reaction(ref r) => r.kind.reagents().iter().map(|r| r.id).collect(),
+            Gene::Receptor(ref r) => vec![r.chemical],
+            Gene::Organ(ref o) => {
+                let mut chemicals = vec![o.toxin, o.repair];
+                chemicals.extend(o.transport.iter().map(|t| t.chemical));
+                for gene in o.genes.iter() {
+                    chemicals.extend(gene.chemicals());
+                }
+                chemicals
+            },
+            Gene::SexLinked(_, ref g) => g.chemicals(),
+            Gene::Brain(..) | Gene::Clock(..) => Vec::new(),
+        }
+    }
+
+    /// The gene itself, or the one it wraps if it is sex-linked.
+    pub fn unlinked(&self) -> &Gene {
+        match *self {
+            Gene::SexLinked(_, ref g) => g.unlinked(),
+            _ => self
+        }
+    }
+
+    /// The gene as expressed in creatures of `sex`, or `None` if it is linked to the other sex.
+    pub fn expressed_in(&self, sex: Sex) -> Option<&Gene> {
+        match *self {
+            Gene::SexLinked(linked, ref g) => if linked == sex {
+                g.expressed_in(sex)
+            } else {
+                None
+            },
+            _ => Some(self)
+        }
+    }
+
+    /// Steps the gene as number `n` of the `genes` in a compartment of the creature, emitting
+    /// `GeneSwitchedOn` if it starts acting and noting it as the cause if it kills the creature.
+    pub fn express(&self, creature: &mut Creature, organ: Option<usize>, n: usize, genes: usize) {
+        let alive = creature.is_alive();
+        let switched = {
+            let (mut body, states) = creature.express(organ, genes);
+            let was = states[n].on;
+            self.step(&mut states[n], &mut body);
+            !was && states[n].on
+        };
+        if switched {
+            creature.emit(Event::GeneSwitchedOn { gene: n, organ: organ });
+        }
+        if let Gene::Receptor(ref r) = *self.unlinked() {
+            if alive && !creature.is_alive() {
+                creature.record_death(DeathCause::Receptor {
+                    gene: n, organ: organ, chemical: r.chemical
+                });
+            }
+        }
+    }
+}
+
+fn weighted(terms: &[(f32, f32)]) -> f32 {
+    let total = terms.iter().fold(0.0, |acc, &(w, _)| acc + w);
+    terms.iter().fold(0.0, |acc, &(w, d)| acc + w * d) / total
+}
+
+/// How the genes of a genome see each other's work within a tick.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TickMode {
+    /// Genes act one after another in genome order, each seeing what the ones before it did.
+    Sequential,
+    /// Genes all read the state the tick started from, so their order makes no difference. See
+    /// `DoubleBuffer` for how their writes are combined.
+    DoubleBuffered,
+}
+
+#[derive(Clone, RustcEncodable)]
 pub struct Genome {
-    genes: Vec<Gene>
+    genes: Vec<Gene>,
+    lineage: Lineage,
+}
+
+impl Decodable for Genome {
+    fn decode<D: Decoder>(d: &mut D) -> ::std::result::Result<Genome, D::Error> {
+        d.read_struct("Genome", 2, |d| {
+            let genes = try!(d.read_struct_field("genes", 0, Decodable::decode));
+            // Genomes saved before lineages were tracked become the roots of new ones.
+            let lineage: Option<Lineage> = try!(d.read_struct_field("lineage", 1,
+                                                                    Decodable::decode));
+            Ok(Genome { genes: genes, lineage: lineage.unwrap_or_else(Lineage::new) })
+        })
+    }
 }
 
 impl Genome {
     pub fn new() -> Genome {
-        Genome { genes: Vec::new() }
+        Genome { genes: Vec::new(), lineage: Lineage::new() }
     }
 
     pub fn from_genes(genes: Vec<Gene>) -> Genome {
-        Genome { genes: genes }
+        Genome { genes: genes, lineage: Lineage::new() }
     }
 
-    pub fn mutate(mut self) -> Genome {
+    pub fn genes(&self) -> &[Gene] {
+        &self.genes
+    }
+
+    pub fn lineage(&self) -> &Lineage {
+        &self.lineage
+    }
+
+    /// A copy of this genome registered as its child, ready to be mutated.
+    pub fn offspring(&self) -> Genome {
+        Genome { genes: self.genes.clone(), lineage: Lineage::descend(&[&self.lineage]) }
+    }
+
+    /// A child of this genome and `other`, taking the gene at each position from one or the other
+    /// at random. Past the end of the shorter genome, the longer one's genes are each inherited
+    /// or not at random.
+    pub fn crossover(&self, other: &Genome) -> Genome {
+        let mut rng = thread_rng();
+        let mut lineage = Lineage::descend(&[&self.lineage, &other.lineage]);
+        let mut genes = Vec::new();
+        for n in 0 .. self.genes.len().max(other.genes.len()) {
+            let (p, parent) = if rng.gen() { (0, self) } else { (1, other) };
+            if let Some(gene) = parent.genes.get(n) {
+                genes.push(gene.clone());
+                lineage.origins.push((p, n));
+            }
+        }
+        Genome { genes: genes, lineage: lineage }
+    }
+
+    /// Appends a gene, recording the insertion in the genome's lineage.
+    pub fn insert_gene(&mut self, gene: Gene) {
+        let index = self.genes.len();
+        self.insert_gene_at(index, gene);
+    }
+
+    /// Inserts a gene at `index`, after the genes before it and before the rest, and records the
+    /// insertion.
+    pub fn insert_gene_at(&mut self, index: usize, gene: Gene) {
+        self.lineage.mutations.push(Mutation::Inserted(index));
+        self.genes.insert(index, gene);
+    }
+
+    /// Removes a gene, keeping the others in order, and records the removal.
+    pub fn remove_gene(&mut self, index: usize) -> Gene {
+        self.lineage.mutations.push(Mutation::Removed(index));
+        self.genes.remove(index)
+    }
+
+    /// Replaces a gene in place, recording the modification.
+    pub fn replace_gene(&mut self, index: usize, gene: Gene) {
+        self.lineage.mutations.push(Mutation::Modified(index));
+        self.genes[index] = gene;
+    }
+
+    pub fn mutate(self) -> Genome {
+        self.mutate_with(Validation::Allow)
+    }
+
+    /// Mutates the genome once, handling any gene the mutation breaks or leaves useless as
+    /// `validation` says. A rejected mutation is retried a few times before the genome is
+    /// returned unchanged.
+    pub fn mutate_with(mut self, validation: Validation) -> Genome {
+        match validation {
+            Validation::Allow => self.mutate_once(),
+            Validation::Reject => {
+                let before = self.validate().len();
+                for _ in 0 .. MUTATION_ATTEMPTS {
+                    let mut candidate = self.clone();
+                    candidate.mutate_once();
+                    if candidate.validate().len() <= before {
+                        return candidate
+                    }
+                }
+            },
+            Validation::Repair => {
+                self.mutate_once();
+                let touched = match self.lineage.mutations.last() {
+                    Some(&Mutation::Inserted(n)) | Some(&Mutation::Modified(n)) => Some(n),
+                    _ => None,
+                };
+                self.repair();
+                if let Some(n) = touched {
+                    if self.validate().iter().any(|d| d.gene == n) {
+                        self.remove_gene(n);
+                    }
+                }
+            },
+        }
+        self
+    }
+
+    fn mutate_once(&mut self) {
         let mut rng = thread_rng();
         let val = rng.gen_range(0, self.genes.len() + 1);
         if val == self.genes.len() {
-            self.genes.push(rng.gen());
+            self.insert_gene(rng.gen());
         } else if rng.gen_weighted_bool(self.genes.len() as u32) {
-            self.genes.swap_remove(val);
+            self.remove_gene(val);
         } else {
-            self.genes[val] = match self.genes[val] {
-                Gene::InitialState(ref ch) => if rng.gen() {
-                    Gene::InitialState(Chemical::with_concentration(ch.id(), rng.gen()))
-                } else {
-                    Gene::InitialState(Chemical::with_concentration(rng.gen(), ch.concnt()))
-                },
-                Gene::Emitter(ref e) => Gene::Emitter(match rng.gen_range(0, 8) {
-                    1 => Emitter { kind: rng.gen(), .. e.clone() },
-                    2 => Emitter { chemical: rng.gen(), .. e.clone() },
-                    3 => Emitter { rate: rng.gen(), .. e.clone() },
-                    4 => Emitter { gain: rng.gen(), .. e.clone() },
-                    5 => Emitter { locus: rng.gen(), .. e.clone() },
-                    6 => Emitter { threshold: rng.gen(), .. e.clone() },
-                    7 => Emitter { clear_after_read: rng.gen(), .. e.clone() },
-                    _ => Emitter { invert: rng.gen(), .. e.clone() },
-                }),
-                Gene::Reaction(ref r) => Gene::Reaction(if rng.gen() {
-                    Reaction { kind: rng.gen(), .. r.clone() }
-                } else {
-                    Reaction { rate: rng.gen(), .. r.clone() }
-                }),
-                Gene::Receptor(ref r) => Gene::Receptor(match rng.gen_range(0, 7) {
-                    1 => Receptor { kind: rng.gen(), .. *r },
-                    2 => Receptor { chemical: rng.gen(), .. *r },
-                    3 => Receptor { locus: rng.gen(), .. *r },
-                    4 => Receptor { nominal: rng.gen(), .. *r },
-                    5 => Receptor { gain: rng.gen(), .. *r },
-                    6 => Receptor { threshold: rng.gen(), .. *r },
-                    _ => Receptor { invert: rng.gen(), .. *r },
-                }),
-                _ => panic!("Something went wrong: failed to mutate a gene.")
-            };
+            let gene = self.genes[val].mutated(&mut rng);
+            self.replace_gene(val, gene);
         }
-        self
+    }
+
+    /// Genetic distance between two genomes, from `0.0` (identical) to `1.0`.
+    pub fn distance(&self, other: &Genome) -> f32 {
+        set_distance(&self.genes, &other.genes)
     }
 
     pub fn load<T: AsRef<Path>>(path: T) -> Result<Genome> {
         let mut f = try!(File::open(path.as_ref()));
         let mut data = String::new();
         try!(f.read_to_string(&mut data));
-        decode(&data).map_err(|_|
-            Error::new(ErrorKind::InvalidInput, "Failed to decode genome.")
+        decode(&data).map_err(|e|
+            Error::new(ErrorKind::InvalidInput, format!("Failed to decode genome: {}.", e))
         )
     }
 
@@ -103,21 +572,100 @@ impl Genome {
     }
 
     pub fn init(&self, creature: &mut Creature) {
-        for gene in self.genes.iter() {
-            if let Gene::InitialState(ref c) = *gene {
-                creature.chemo_body_mut().gain(c.id(), c.concnt());
+        let sex = creature.sex();
+        for gene in self.genes.iter().filter_map(|g| g.expressed_in(sex)) {
+            match *gene {
+                Gene::InitialState(ref c) => {
+                    creature.chemo_body_mut().gain(c.id(), c.concnt());
+                },
+                Gene::Organ(ref o) => {
+                    let organ = creature.add_organ();
+                    o.init(creature, organ);
+                },
+                _ => ()
             }
         }
     }
 
-    pub fn step(&self, creature: &mut Creature) {
-        for gene in self.genes.iter() {
-            match *gene {
-                Gene::Emitter(ref e) => e.step(creature),
-                Gene::Reaction(ref r) => r.step(creature),
-                Gene::Receptor(ref r) => r.step(creature),
-                _ => ()
+    /// The clock the genome's creatures of `sex` age by.
+    pub fn clock(&self, sex: Sex) -> LifeClock {
+        self.genes.iter().filter_map(|gene| match gene.expressed_in(sex) {
+            Some(&Gene::Clock(c)) => Some(c),
+            _ => None,
+        }).next().unwrap_or(LifeClock::default())
+    }
+
+    /// Steps the creature, returning the stage of life it has moved on to, if any.
+    pub fn step(&self, creature: &mut Creature) -> Option<Age> {
+        self.tick(creature, TickMode::Sequential)
+    }
+
+    /// Steps the creature with its genes interacting as `mode` says, emitting events for whatever
+    /// happened. The life clock goes first, so that genes see the stage the creature is in.
+    pub fn tick(&self, creature: &mut Creature, mode: TickMode) -> Option<Age> {
+        let vitals = Vitals::last(creature);
+        let age = creature.age();
+        let sex = creature.sex();
+        let clock = self.clock(sex);
+        clock.step(creature);
+        creature.restore_sex_locus();
+        let mut buffer = match mode {
+            TickMode::Sequential => None,
+            TickMode::DoubleBuffered => Some(DoubleBuffer::new(creature)),
+        };
+        let mut organs = 0;
+        // Organs are numbered as `init` gave them compartments, skipping those of the other sex.
+        for (n, gene) in self.genes.iter().enumerate() {
+            match gene.expressed_in(sex) {
+                Some(&Gene::Organ(ref o)) => {
+                    o.step(creature, organs, mode);
+                    organs += 1;
+                },
+                Some(_) => gene.express(creature, None, n, self.genes.len()),
+                None => (),
             }
+            if let Some(ref mut buffer) = buffer {
+                buffer.collect(creature, &gene.chemicals());
+            }
+        }
+        if let Some(buffer) = buffer {
+            buffer.swap(creature);
+        }
+        clock.hold(creature);
+        creature.hold_death();
+        vitals.report(creature);
+        if creature.age() != age { Some(creature.age()) } else { None }
+    }
+
+    /// Steps the creature, then shows the result to `observer`.
+    pub fn step_with<O: Observer>(&self, creature: &mut Creature, observer: &mut O)
+                                  -> Option<Age> {
+        let stage = self.step(creature);
+        observer.observe(creature);
+        stage
+    }
+}
+
+/// Genetic distance between two sets of genes. Each gene is paired with the closest unpaired gene
+/// of the same kind in `b`; unpaired genes on either side count as fully different.
+fn set_distance(a: &[Gene], b: &[Gene]) -> f32 {
+    if a.is_empty() && b.is_empty() { return 0.0 }
+    let mut paired = vec![false; b.len()];
+    let mut total = 0.0;
+    let mut matches = 0;
+    for gene in a.iter() {
+        let closest = b.iter().enumerate().filter(|&(n, _)| !paired[n])
+            .filter_map(|(n, g)| gene.distance(g).map(|d| (n, d)))
+            .fold(None, |best: Option<(usize, f32)>, (n, d)| match best {
+                Some((_, bd)) if bd <= d => best,
+                _ => Some((n, d)),
+            });
+        if let Some((n, d)) = closest {
+            paired[n] = true;
+            total += d;
+            matches += 1;
         }
     }
+    let unpaired = (a.len() - matches) + (b.len() - matches);
+    (total + unpaired as f32) / (matches + unpaired) as f32
 }
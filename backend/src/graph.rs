@@ -0,0 +1,224 @@
+use chem::{Id, IoType, Kinetics, ReactionType};
+use creature::LocusId;
+use dsl::Names;
+use genome::{Gene, Genome};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Node {
+    Chemical(Id),
+    Locus(LocusId),
+    /// A reaction, identified by the index of its gene.
+    Reaction(usize),
+}
+
+impl Node {
+    fn key(&self) -> String {
+        match *self {
+            Node::Chemical(id) => format!("c{}", id),
+            Node::Locus(id) => format!("l{}", id),
+            Node::Reaction(n) => format!("r{}", n),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EdgeKind {
+    /// An emitter turning a locus into a chemical. Inverted emitters, which emit more the lower
+    /// the locus, are labelled with the locus value they start emitting at or below.
+    Emits,
+    /// A receptor turning a chemical into a locus, labelled with a negative gain if inverted.
+    Senses,
+    Consumes,
+    Produces,
+    /// A catalyst, which a reaction needs but does not use up.
+    Catalyses,
+}
+
+#[derive(Clone, Debug)]
+pub struct Edge {
+    pub from: Node,
+    pub to: Node,
+    pub kind: EdgeKind,
+    pub label: String,
+}
+
+/// The biochemistry of a genome as a bipartite graph: chemicals and loci on one side, and
+/// reactions on the other, joined by emitters, receptors and stoichiometry. Only the bloodstream
+/// is shown, so organs and the genes inside them are left out.
+#[derive(Clone, Debug)]
+pub struct Network {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+    /// Initial concentrations of chemicals, by id.
+    pub initial: Vec<(Id, u8)>,
+    reactions: Vec<(usize, String)>,
+}
+
+impl Network {
+    pub fn from_genome(genome: &Genome) -> Network {
+        let mut network = Network {
+            nodes: Vec::new(), edges: Vec::new(), initial: Vec::new(), reactions: Vec::new()
+        };
+        for (n, gene) in genome.genes().iter().enumerate() {
+            match *gene.unlinked() {
+                Gene::InitialState(ref c) => {
+                    network.node(Node::Chemical(c.id()));
+                    network.initial.push((c.id(), c.concnt()));
+                },
+                Gene::Emitter(ref e) => {
+                    let label = if e.invert {
+                        format!("{} <= {}: +{} (inverted)", io(e.kind), 255 - e.threshold, e.gain)
+                    } else {
+                        format!("{} >= {}: +{}", io(e.kind), e.threshold, e.gain)
+                    };
+                    network.edge(Node::Locus(e.locus), Node::Chemical(e.chemical),
+                                 EdgeKind::Emits, label);
+                },
+                Gene::Reaction(ref r) => {
+                    let reaction = Node::Reaction(n);
+                    network.node(reaction);
+                    let kinetics = match r.kinetics {
+                        Kinetics::AllOrNothing => String::new(),
+                        Kinetics::MassAction(k) => format!(", k = {}", k),
+                    };
+                    network.reactions.push((n, format!("{} every {}{}", r.kind.name(), r.rate,
+                                                       kinetics)));
+                    // A catalyst comes first on both sides of the reaction, and may share its id
+                    // with another reagent, so it is told apart by position.
+                    let catalysed = match r.kind {
+                        ReactionType::Catalytic(..) | ReactionType::CatalyticBreakdown(..) => true,
+                        _ => false,
+                    };
+                    for (i, reagent) in r.kind.reactants().into_iter().enumerate() {
+                        let kind = if catalysed && i == 0 {
+                            EdgeKind::Catalyses
+                        } else {
+                            EdgeKind::Consumes
+                        };
+                        network.edge(Node::Chemical(reagent.id), reaction, kind,
+                                     reagent.coefficient.get().to_string());
+                    }
+                    let products = r.kind.products().into_iter().enumerate();
+                    for (_, reagent) in products.filter(|&(i, _)| !catalysed || i > 0) {
+                        network.edge(reaction, Node::Chemical(reagent.id), EdgeKind::Produces,
+                                     reagent.coefficient.get().to_string());
+                    }
+                },
+                Gene::Receptor(ref r) => {
+                    let label = format!("{} > {}: {}{} from {}", io(r.kind), r.threshold,
+                                        if r.invert { "-" } else { "+" }, r.gain, r.nominal);
+                    network.edge(Node::Chemical(r.chemical), Node::Locus(r.locus),
+                                 EdgeKind::Senses, label);
+                },
+                // Organs have compartments of their own, which this graph does not show.
+                Gene::Brain(..) | Gene::Organ(..) | Gene::Clock(..) |
+                Gene::SexLinked(..) => (),
+            }
+        }
+        network
+    }
+
+    fn node(&mut self, node: Node) {
+        if !self.nodes.contains(&node) {
+            self.nodes.push(node);
+        }
+    }
+
+    fn edge(&mut self, from: Node, to: Node, kind: EdgeKind, label: String) {
+        self.node(from);
+        self.node(to);
+        self.edges.push(Edge { from: from, to: to, kind: kind, label: label });
+    }
+
+    fn label(&self, node: Node, names: &Names) -> String {
+        match node {
+            Node::Chemical(id) => match self.initial.iter().find(|&&(c, _)| c == id) {
+                Some(&(_, concnt)) => format!("{} = {}", names.chemical(id), concnt),
+                None => names.chemical(id),
+            },
+            Node::Locus(id) => names.locus(id),
+            Node::Reaction(n) => {
+                self.reactions.iter().find(|&&(r, _)| r == n).map_or(String::new(), |r| {
+                    format!("#{}: {}", n, r.1)
+                })
+            },
+        }
+    }
+
+    /// Renders the network in Graphviz's DOT language.
+    pub fn to_dot(&self, names: &Names) -> String {
+        let mut out = String::from("digraph genome {\n");
+        for &node in self.nodes.iter() {
+            let shape = match node {
+                Node::Chemical(_) => "ellipse",
+                Node::Locus(_) => "diamond",
+                Node::Reaction(_) => "box",
+            };
+            out.push_str(&format!("    {} [label=\"{}\", shape={}];\n", node.key(),
+                                  escape_dot(&self.label(node, names)), shape));
+        }
+        for edge in self.edges.iter() {
+            let style = match edge.kind {
+                EdgeKind::Emits | EdgeKind::Senses => "dashed",
+                EdgeKind::Catalyses => "dotted",
+                EdgeKind::Consumes | EdgeKind::Produces => "solid",
+            };
+            out.push_str(&format!("    {} -> {} [label=\"{}\", style={}];\n", edge.from.key(),
+                                  edge.to.key(), escape_dot(&edge.label), style));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Renders the network as GraphML.
+    pub fn to_graphml(&self, names: &Names) -> String {
+        let mut out = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"kind\" for=\"all\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+            "  <key id=\"label\" for=\"all\" attr.name=\"label\" attr.type=\"string\"/>\n",
+            "  <graph id=\"genome\" edgedefault=\"directed\">\n",
+        ));
+        for &node in self.nodes.iter() {
+            let kind = match node {
+                Node::Chemical(_) => "chemical",
+                Node::Locus(_) => "locus",
+                Node::Reaction(_) => "reaction",
+            };
+            out.push_str(&format!(concat!(
+                "    <node id=\"{}\"><data key=\"kind\">{}</data>",
+                "<data key=\"label\">{}</data></node>\n"
+            ), node.key(), kind, escape_xml(&self.label(node, names))));
+        }
+        for edge in self.edges.iter() {
+            let kind = match edge.kind {
+                EdgeKind::Emits => "emits",
+                EdgeKind::Senses => "senses",
+                EdgeKind::Consumes => "consumes",
+                EdgeKind::Produces => "produces",
+                EdgeKind::Catalyses => "catalyses",
+            };
+            out.push_str(&format!(concat!(
+                "    <edge source=\"{}\" target=\"{}\"><data key=\"kind\">{}</data>",
+                "<data key=\"label\">{}</data></edge>\n"
+            ), edge.from.key(), edge.to.key(), kind, escape_xml(&edge.label)));
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+}
+
+fn io(kind: IoType) -> &'static str {
+    match kind {
+        IoType::Analogue => "analogue",
+        IoType::Digital => "digital",
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,194 @@
+use std::cmp::min;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result};
+use std::io::prelude::*;
+use std::path::Path;
+use chem::{Concentration, Id};
+use creature::Creature;
+use rustc_serialize::json::{decode, encode};
+
+/// How many units of each chemical pass from the gut into the bloodstream per tick.
+const DIGESTION_RATE: Concentration = 4;
+
+/// A named mixture of chemicals, such as a drug or the contents of a food.
+#[derive(Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub struct Dose {
+    pub name: String,
+    pub chemicals: Vec<(Id, Concentration)>,
+}
+
+impl Dose {
+    pub fn new(name: &str) -> Dose {
+        Dose { name: name.to_owned(), chemicals: Vec::new() }
+    }
+
+    pub fn with(mut self, chemical: Id, amount: Concentration) -> Dose {
+        self.chemicals.push((chemical, amount));
+        self
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub enum Route {
+    /// Straight into the bloodstream.
+    Injection,
+    /// Into the gut, to be digested into the bloodstream a little every tick.
+    Ingestion,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Schedule {
+    At(u64),
+    Every { start: u64, period: u64 },
+}
+
+impl Schedule {
+    fn due(&self, tick: u64) -> bool {
+        match *self {
+            Schedule::At(at) => tick == at,
+            Schedule::Every { start, period } => tick >= start && (tick - start) % period == 0,
+        }
+    }
+}
+
+/// A dose that was given, and when.
+#[derive(Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub struct Injection {
+    pub tick: u64,
+    pub route: Route,
+    pub dose: Dose,
+}
+
+/// Everything an injector gave a creature, in order, so that a run can be replayed.
+#[derive(Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub struct InjectionLog {
+    pub injections: Vec<Injection>,
+}
+
+impl InjectionLog {
+    pub fn load<T: AsRef<Path>>(path: T) -> Result<InjectionLog> {
+        let mut f = try!(File::open(path.as_ref()));
+        let mut data = String::new();
+        try!(f.read_to_string(&mut data));
+        decode(&data).map_err(|_|
+            Error::new(ErrorKind::InvalidInput, "Failed to decode injection log.")
+        )
+    }
+
+    pub fn save<T: AsRef<Path>>(&self, path: T) -> Result<()> {
+        let mut f = try!(File::create(path.as_ref()));
+        try!(f.write_all(try!(encode(self).map_err(|_|
+            Error::new(ErrorKind::InvalidInput, "Failed to encode injection log.")
+        )).as_bytes()));
+        f.flush()
+    }
+}
+
+/// Adds chemicals to a creature from outside, whether by hand, on a schedule or by feeding it.
+/// Call `step` once per tick, before stepping the creature's genome.
+pub struct Injector {
+    tick: u64,
+    schedule: Vec<(Schedule, Route, Dose)>,
+    gut: Vec<(Id, Concentration)>,
+    log: InjectionLog,
+}
+
+impl Injector {
+    pub fn new() -> Injector {
+        Injector {
+            tick: 0, schedule: Vec::new(), gut: Vec::new(),
+            log: InjectionLog { injections: Vec::new() }
+        }
+    }
+
+    /// An injector that gives exactly the doses in `log`, at the same ticks and by the same
+    /// routes.
+    pub fn replay(log: &InjectionLog) -> Injector {
+        let mut injector = Injector::new();
+        for injection in log.injections.iter() {
+            injector.schedule(Schedule::At(injection.tick), injection.route,
+                              injection.dose.clone());
+        }
+        injector
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn log(&self) -> &InjectionLog {
+        &self.log
+    }
+
+    /// Takes every dose given so far out of the log, for an injector that runs too long to keep
+    /// them all.
+    pub fn drain_log(&mut self) -> Vec<Injection> {
+        self.log.injections.drain(..).collect()
+    }
+
+    /// How much of a chemical is in the gut, waiting to be digested.
+    pub fn in_gut(&self, chemical: Id) -> Concentration {
+        self.gut.iter().find(|&&(c, _)| c == chemical).map_or(0, |&(_, held)| held)
+    }
+
+    pub fn schedule(&mut self, schedule: Schedule, route: Route, dose: Dose) {
+        if let Schedule::Every { period, .. } = schedule {
+            assert!(period > 0, "injection period must be positive");
+        }
+        self.schedule.push((schedule, route, dose));
+    }
+
+    /// Gives a dose now.
+    pub fn give(&mut self, creature: &mut Creature, route: Route, dose: &Dose) {
+        match route {
+            Route::Injection => {
+                let body = creature.compartment_mut(None);
+                for &(chemical, amount) in dose.chemicals.iter() {
+                    body.gain(chemical, amount);
+                }
+            },
+            Route::Ingestion => for &(chemical, amount) in dose.chemicals.iter() {
+                match self.gut.iter_mut().find(|&&mut (c, _)| c == chemical) {
+                    Some(&mut (_, ref mut held)) => *held = held.saturating_add(amount),
+                    None => self.gut.push((chemical, amount)),
+                }
+            },
+        }
+        self.log.injections.push(Injection { tick: self.tick, route: route, dose: dose.clone() });
+    }
+
+    pub fn inject(&mut self, creature: &mut Creature, dose: &Dose) {
+        self.give(creature, Route::Injection, dose);
+    }
+
+    /// Feeds the creature a food, which it digests over the following ticks.
+    pub fn feed(&mut self, creature: &mut Creature, food: &Dose) {
+        self.give(creature, Route::Ingestion, food);
+    }
+
+    /// Gives any scheduled doses that are due, digests some of what is in the gut and moves on
+    /// to the next tick.
+    pub fn step(&mut self, creature: &mut Creature) {
+        let due: Vec<(Route, Dose)> = self.schedule.iter().filter(|&&(ref s, _, _)| {
+            s.due(self.tick)
+        }).map(|&(_, route, ref dose)| (route, dose.clone())).collect();
+        for (route, dose) in due {
+            self.give(creature, route, &dose);
+        }
+        {
+            let body = creature.compartment_mut(None);
+            for &mut (chemical, ref mut held) in self.gut.iter_mut() {
+                let amount = min(*held, DIGESTION_RATE);
+                body.gain(chemical, amount);
+                *held -= amount;
+            }
+        }
+        self.gut.retain(|&(_, held)| held > 0);
+        let tick = self.tick;
+        self.schedule.retain(|&(s, _, _)| match s {
+            Schedule::At(at) => at > tick,
+            Schedule::Every { .. } => true,
+        });
+        self.tick += 1;
+    }
+}
@@ -1,12 +1,48 @@
 extern crate rand;
 extern crate rustc_serialize;
 
+pub mod analysis;
 pub mod brain;
+pub mod breeding;
 pub mod chem;
 pub mod creature;
+pub mod death;
+pub mod diff;
+pub mod dsl;
+pub mod events;
 pub mod genome;
+pub mod graph;
+pub mod inject;
+pub mod life;
+pub mod lineage;
+pub mod organ;
+pub mod pareto;
+pub mod plot;
+pub mod population;
+pub mod recorder;
+pub mod species;
+pub mod validate;
+pub mod world;
 
+pub use analysis::*;
 pub use brain::*;
+pub use breeding::*;
 pub use chem::*;
 pub use creature::*;
+pub use death::*;
+pub use diff::*;
+pub use dsl::*;
+pub use events::*;
 pub use genome::*;
+pub use graph::*;
+pub use inject::*;
+pub use life::*;
+pub use lineage::*;
+pub use organ::*;
+pub use pareto::*;
+pub use plot::*;
+pub use population::*;
+pub use recorder::*;
+pub use species::*;
+pub use validate::*;
+pub use world::*;
@@ -0,0 +1,105 @@
+use creature::{Age, Creature, Locus};
+use rand::Rng;
+
+/// The loci marking each stage after the first, in order.
+pub const STAGE_LOCI: [Locus; 6] = [
+    Locus::AgedToChild, Locus::AgedToAdolescent, Locus::AgedToYouth, Locus::AgedToAdult,
+    Locus::AgedToOld, Locus::AgedToSenile,
+];
+
+/// Moves a creature through the stages of life as it gets older. Each duration is how many ticks
+/// the creature spends in a stage, from `Baby` through `Old`, before moving on to the next; once
+/// `Senile` it stays so. The loci for every stage reached are set to 255 both before and after
+/// the genes act each tick, so chemistry can hurry a creature along but not make it younger.
+/// Mutation changes the durations of a genome's clock but never adds one, since `Gene::rand`
+/// makes no clocks.
+#[derive(Copy, Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub struct LifeClock {
+    pub durations: [u16; 6],
+}
+
+impl Default for LifeClock {
+    fn default() -> LifeClock {
+        LifeClock::new([50, 50, 50, 100, 200, 100])
+    }
+}
+
+impl LifeClock {
+    pub fn new(durations: [u16; 6]) -> LifeClock {
+        LifeClock { durations: durations }
+    }
+
+    /// The stage a creature reaches after living `ticks` ticks.
+    pub fn stage_at(&self, ticks: u64) -> Age {
+        let mut end = 0;
+        for (n, &duration) in self.durations.iter().enumerate() {
+            end += duration as u64;
+            if ticks < end { return Age::from_stage(n) }
+        }
+        Age::Senile
+    }
+
+    /// Makes the creature a tick older and sets the loci of every stage it has reached.
+    pub fn step(&self, creature: &mut Creature) {
+        creature.grow_older();
+        self.hold(creature);
+    }
+
+    /// Sets the loci of every stage the creature has reached again, undoing any gene that has
+    /// cleared them.
+    pub fn hold(&self, creature: &mut Creature) {
+        let stage = self.stage_at(creature.lived()) as usize;
+        for &locus in STAGE_LOCI[.. stage].iter() {
+            creature.set_locus(locus as u8, 255);
+        }
+    }
+
+    /// The same clock with one stage made up to twice as long or as short.
+    pub fn mutate<R: Rng>(&self, rng: &mut R) -> LifeClock {
+        let mut clock = *self;
+        let n = rng.gen_range(0, clock.durations.len());
+        let duration = clock.durations[n] as u32;
+        clock.durations[n] = rng.gen_range(duration / 2, duration * 2 + 2).min(0xffff) as u16;
+        clock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use creature::{Age, Creature, Sex};
+    use dsl::parse_genome;
+    use events::Event;
+
+    #[test]
+    fn genes_cannot_make_a_creature_younger() {
+        let (genome, _) = parse_genome("clock 2 2 2 2 2 2
+initial 1 10
+receptor digital chem 1 > 0 -> locus aged_to_child nominal 0 gain 0").unwrap();
+        let mut creature = Creature::new();
+        genome.init(&mut creature);
+        for _ in 0 .. 4 {
+            genome.step(&mut creature);
+        }
+        assert_eq!(creature.age(), Age::Adolescent);
+        let stages: Vec<(Age, Age)> = creature.drain_events().into_iter().filter_map(|(_, e)| {
+            match e {
+                Event::StageChanged { from, to } => Some((from, to)),
+                _ => None,
+            }
+        }).collect();
+        assert_eq!(stages, vec![(Age::Baby, Age::Child), (Age::Child, Age::Adolescent)]);
+    }
+
+    #[test]
+    fn sex_linked_clocks_only_age_their_sex() {
+        let (genome, _) = parse_genome("female clock 1 1 1 1 1 1").unwrap();
+        let mut female = Creature::with_sex(Sex::Female);
+        let mut male = Creature::with_sex(Sex::Male);
+        for _ in 0 .. 2 {
+            genome.step(&mut female);
+            genome.step(&mut male);
+        }
+        assert_eq!(female.age(), Age::Adolescent);
+        assert_eq!(male.age(), Age::Baby);
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result};
+use std::io::prelude::*;
+use std::path::Path;
+use genome::Genome;
+use rand::{thread_rng, Rng};
+use rustc_serialize::{Decodable, Decoder};
+use rustc_serialize::json::{decode, encode};
+
+#[derive(Copy, Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub enum Mutation {
+    /// A new gene was inserted at this index, moving the genes after it up one.
+    Inserted(usize),
+    /// The gene at this index was removed, moving the genes after it down one.
+    Removed(usize),
+    /// The gene at this index was altered.
+    Modified(usize),
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Mutation::Inserted(n) => write!(f, "+{}", n),
+            Mutation::Removed(n) => write!(f, "-{}", n),
+            Mutation::Modified(n) => write!(f, "~{}", n),
+        }
+    }
+}
+
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct Lineage {
+    pub id: u64,
+    pub parents: Vec<u64>,
+    pub generation: u32,
+    pub mutations: Vec<Mutation>,
+    /// Where each gene of a genome born by crossover came from, as the position of the parent in
+    /// `parents` and the gene's index there. Empty for other genomes, whose genes start out where
+    /// their first parent's were.
+    pub origins: Vec<(usize, usize)>,
+}
+
+impl Decodable for Lineage {
+    fn decode<D: Decoder>(d: &mut D) -> ::std::result::Result<Lineage, D::Error> {
+        d.read_struct("Lineage", 5, |d| {
+            let id = try!(d.read_struct_field("id", 0, Decodable::decode));
+            let parents = try!(d.read_struct_field("parents", 1, Decodable::decode));
+            let generation = try!(d.read_struct_field("generation", 2, Decodable::decode));
+            let mutations = try!(d.read_struct_field("mutations", 3, Decodable::decode));
+            // Lineages saved before origins were recorded.
+            let origins: Option<Vec<(usize, usize)>> = try!(d.read_struct_field("origins", 4,
+                                                                                Decodable::decode));
+            Ok(Lineage {
+                id: id, parents: parents, generation: generation, mutations: mutations,
+                origins: origins.unwrap_or_else(Vec::new)
+            })
+        })
+    }
+}
+
+impl Lineage {
+    pub fn new() -> Lineage {
+        Lineage {
+            id: thread_rng().gen(), parents: Vec::new(), generation: 0, mutations: Vec::new(),
+            origins: Vec::new()
+        }
+    }
+
+    pub fn descend(parents: &[&Lineage]) -> Lineage {
+        Lineage {
+            id: thread_rng().gen(),
+            parents: parents.iter().map(|p| p.id).collect(),
+            generation: parents.iter().map(|p| p.generation + 1).max().unwrap_or(0),
+            mutations: Vec::new(),
+            origins: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Lineage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{:016x} (generation {})", self.id, self.generation));
+        for (n, mutation) in self.mutations.iter().enumerate() {
+            try!(write!(f, "{}{}", if n == 0 { " " } else { "," }, mutation));
+        }
+        Ok(())
+    }
+}
+
+/// A record of ancestry, keyed by genome id.
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct Pedigree {
+    lineages: BTreeMap<u64, Lineage>,
+}
+
+impl Pedigree {
+    pub fn new() -> Pedigree {
+        Pedigree { lineages: BTreeMap::new() }
+    }
+
+    pub fn record(&mut self, genome: &Genome) {
+        let lineage = genome.lineage();
+        self.lineages.insert(lineage.id, lineage.clone());
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Lineage> {
+        self.lineages.get(&id)
+    }
+
+    /// Forgets every lineage that is not an ancestor of (or one of) the given ids.
+    pub fn prune(&mut self, keep: &[u64]) {
+        let mut wanted = BTreeSet::new();
+        let mut queue = keep.to_vec();
+        while let Some(id) = queue.pop() {
+            if !wanted.insert(id) { continue }
+            if let Some(lineage) = self.lineages.get(&id) {
+                queue.extend(lineage.parents.iter().cloned());
+            }
+        }
+        self.lineages = self.lineages.iter().filter(|&(id, _)| wanted.contains(id))
+                                     .map(|(&id, l)| (id, l.clone())).collect();
+    }
+
+    /// Renders the recorded lineages as a family tree, oldest ancestors first. Chains of only
+    /// children stay at the same depth so that long runs remain readable.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let roots = self.lineages.values().filter(|l| {
+            l.parents.iter().all(|p| !self.lineages.contains_key(p))
+        });
+        for root in roots {
+            self.render_from(root, 0, &mut out);
+        }
+        out
+    }
+
+    fn render_from(&self, lineage: &Lineage, depth: usize, out: &mut String) {
+        out.push_str(&format!("{:2$}{}\n", "", lineage, depth * 2));
+        let children: Vec<&Lineage> = self.lineages.values().filter(|l| {
+            l.parents.first() == Some(&lineage.id)
+        }).collect();
+        let depth = if children.len() > 1 { depth + 1 } else { depth };
+        for child in children {
+            self.render_from(child, depth, out);
+        }
+    }
+
+    /// Traces the gene at `index` in `genome` back through its ancestors. Returns every mutation
+    /// that touched it, oldest first, along with the lineage it happened in. The history starts
+    /// with an insertion unless the gene predates the recorded pedigree.
+    pub fn trace_gene<'a>(&'a self, genome: &'a Genome, mut index: usize)
+                          -> Vec<(&'a Lineage, Mutation)> {
+        let mut history = Vec::new();
+        if index >= genome.genes().len() { return history }
+        let mut current = Some(genome.lineage());
+        while let Some(lineage) = current {
+            // Undo each mutation in turn, keeping track of where the gene was before it.
+            for &mutation in lineage.mutations.iter().rev() {
+                match mutation {
+                    Mutation::Inserted(n) => {
+                        if n == index {
+                            history.push((lineage, mutation));
+                            history.reverse();
+                            return history;
+                        }
+                        if n < index { index -= 1 }
+                    },
+                    Mutation::Removed(n) => if n <= index { index += 1 },
+                    Mutation::Modified(n) => if n == index {
+                        history.push((lineage, mutation));
+                    },
+                }
+            }
+            let parent = if lineage.origins.is_empty() {
+                lineage.parents.first()
+            } else {
+                lineage.origins.get(index).and_then(|&(parent, n)| {
+                    index = n;
+                    lineage.parents.get(parent)
+                })
+            };
+            current = parent.and_then(|p| self.lineages.get(p));
+        }
+        history.reverse();
+        history
+    }
+
+    pub fn load<T: AsRef<Path>>(path: T) -> Result<Pedigree> {
+        let mut f = try!(File::open(path.as_ref()));
+        let mut data = String::new();
+        try!(f.read_to_string(&mut data));
+        decode(&data).map_err(|_|
+            Error::new(ErrorKind::InvalidInput, "Failed to decode pedigree.")
+        )
+    }
+
+    pub fn save<T: AsRef<Path>>(&self, path: T) -> Result<()> {
+        let mut f = try!(File::create(path.as_ref()));
+        try!(f.write_all(try!(encode(self).map_err(|_|
+            Error::new(ErrorKind::InvalidInput, "Failed to encode pedigree.")
+        )).as_bytes()));
+        f.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use genome::{Gene, Genome};
+    use life::LifeClock;
+    use super::*;
+
+    fn parent() -> Genome {
+        let mut genome = Genome::new();
+        for n in 0 .. 3 {
+            genome.insert_gene(Gene::Clock(LifeClock { durations: [n; 6] }));
+        }
+        genome
+    }
+
+    #[test]
+    fn genes_are_traced_through_either_parent() {
+        let (parent, other) = (parent(), Genome::new());
+        let mut pedigree = Pedigree::new();
+        pedigree.record(&parent);
+        pedigree.record(&other);
+        for &(a, b) in [(&parent, &other), (&other, &parent)].iter() {
+            let child = (0 ..).map(|_| a.crossover(b)).find(|c| c.genes().len() == 1).unwrap();
+            let origin = match child.genes()[0] {
+                Gene::Clock(ref c) => c.durations[0] as usize,
+                _ => unreachable!(),
+            };
+            let history = pedigree.trace_gene(&child, 0);
+            assert_eq!(history.len(), 1);
+            assert_eq!(history[0].0.id, parent.lineage().id);
+            assert_eq!(history[0].1, Mutation::Inserted(origin));
+        }
+    }
+
+    #[test]
+    fn genes_are_traced_past_removals() {
+        let parent = parent();
+        let mut child = parent.offspring();
+        child.remove_gene(0);
+        child.insert_gene_at(0, Gene::Clock(LifeClock { durations: [9; 6] }));
+        let mut pedigree = Pedigree::new();
+        pedigree.record(&parent);
+        let history = pedigree.trace_gene(&child, 2);
+        assert_eq!(history.last().map(|h| h.1), Some(Mutation::Inserted(2)));
+        assert!(pedigree.trace_gene(&child, 3).is_empty());
+    }
+}
@@ -0,0 +1,206 @@
+use std::cmp::min;
+use chem::{Concentration, Id};
+use creature::{Creature, DoubleBuffer, Locus};
+use death::DeathCause;
+use genome::{Gene, TickMode};
+use rand::{Rand, Rng};
+
+#[derive(Copy, Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub enum Direction {
+    /// From the bloodstream into the organ.
+    Import,
+    /// From the organ into the bloodstream.
+    Export,
+}
+
+impl Rand for Direction {
+    fn rand<R: Rng>(rng: &mut R) -> Direction {
+        if rng.gen() {
+            Direction::Import
+        } else {
+            Direction::Export
+        }
+    }
+}
+
+/// Moves up to `rate` units of a chemical across the organ's wall every tick.
+#[derive(Copy, Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub struct Transport {
+    pub chemical: Id,
+    pub rate: Concentration,
+    pub direction: Direction,
+}
+
+impl Rand for Transport {
+    fn rand<R: Rng>(rng: &mut R) -> Transport {
+        Transport::new(rng.gen(), rng.gen(), rng.gen())
+    }
+}
+
+impl Transport {
+    pub fn new(chemical: Id, rate: Concentration, direction: Direction) -> Transport {
+        Transport { chemical: chemical, rate: rate, direction: direction }
+    }
+}
+
+/// A compartment with a chemistry of its own, separate from the bloodstream. Its genes act on
+/// its own chemicals while loci stay shared with the rest of the creature. Every unit of toxin
+/// that gets into the organ costs it a point of life force, and every unit of the repair chemical
+/// restores one; an organ with no life force left stops working, and a failed vital organ kills.
+#[derive(Clone, RustcEncodable, RustcDecodable)]
+pub struct Organ {
+    pub genes: Vec<Gene>,
+    pub vital: bool,
+    pub toxin: Id,
+    pub repair: Id,
+    pub transport: Vec<Transport>,
+}
+
+impl Organ {
+    pub fn new(genes: Vec<Gene>, vital: bool, toxin: Id, repair: Id, transport: Vec<Transport>)
+               -> Organ {
+        Organ { genes: genes, vital: vital, toxin: toxin, repair: repair, transport: transport }
+    }
+
+    /// Fills the organ's compartment with its initial chemicals.
+    pub fn init(&self, creature: &mut Creature, organ: usize) {
+        let sex = creature.sex();
+        let body = creature.compartment_mut(Some(organ));
+        for gene in self.genes.iter().filter_map(|g| g.expressed_in(sex)) {
+            if let Gene::InitialState(ref c) = *gene {
+                body.gain(c.id(), c.concnt());
+            }
+        }
+    }
+
+    /// Steps the organ, with its genes seeing each other's work as `mode` says. Transport happens
+    /// before they act and damage after.
+    pub fn step(&self, creature: &mut Creature, organ: usize, mode: TickMode) {
+        if creature.life_force(organ) == 0 { return }
+        for transport in self.transport.iter() {
+            let (blood, inner) = creature.exchange(organ);
+            let (source, sink) = match transport.direction {
+                Direction::Import => (blood, inner),
+                Direction::Export => (inner, blood),
+            };
+            let amount = min(transport.rate, min(source.concnt(transport.chemical),
+                                                 255 - sink.concnt(transport.chemical)));
+            source.lose(transport.chemical, amount);
+            sink.gain(transport.chemical, amount);
+        }
+
+        // Genes inside an organ never contain organs of their own, see `Reason::NestedOrgan`.
+        let mut buffer = match mode {
+            TickMode::Sequential => None,
+            TickMode::DoubleBuffered => Some(DoubleBuffer::new(creature)),
+        };
+        for (n, gene) in self.genes.iter().enumerate() {
+            gene.express(creature, Some(organ), n, self.genes.len());
+            if let Some(ref mut buffer) = buffer {
+                buffer.collect(creature, &gene.chemicals());
+            }
+        }
+        if let Some(buffer) = buffer {
+            buffer.swap(creature);
+        }
+
+        let mut life = creature.life_force(organ);
+        {
+            let body = creature.compartment_mut(Some(organ));
+            let damage = min(body.concnt(self.toxin), life);
+            body.lose(self.toxin, damage);
+            life -= damage;
+            if life > 0 {
+                let repair = min(body.concnt(self.repair), 255 - life);
+                body.lose(self.repair, repair);
+                life += repair;
+            }
+        }
+        creature.set_life_force(organ, life);
+        if life == 0 && self.vital {
+            if creature.is_alive() {
+                creature.record_death(DeathCause::OrganFailure(organ));
+            }
+            creature.set_locus(Locus::Death as u8, 255);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use creature::{Creature, Locus, Sex};
+    use death::DeathCause;
+    use dsl::parse_genome;
+    use genome::{Gene, Genome, TickMode};
+
+    /// A creature of `text` after a tick in each mode.
+    fn ticked(text: &str) -> Vec<Creature> {
+        let genome = parse_genome(text).unwrap().0;
+        [TickMode::Sequential, TickMode::DoubleBuffered].iter().map(|&mode| {
+            let mut creature = Creature::with_sex(Sex::Female);
+            genome.init(&mut creature);
+            genome.tick(&mut creature, mode);
+            creature
+        }).collect()
+    }
+
+    #[test]
+    fn poisoned_organs_fail() {
+        for creature in ticked("organ toxin 9 repair 8
+    initial 9 255
+end") {
+            assert_eq!(creature.life_force(0), 0);
+            assert!(creature.is_alive());
+        }
+        for creature in ticked("organ vital toxin 9 repair 8
+    initial 9 200
+    initial 8 100
+end") {
+            // The repair chemical heals what the toxin did, up to full life force.
+            assert_eq!(creature.life_force(0), 155);
+        }
+    }
+
+    #[test]
+    fn vital_organ_failure_is_final() {
+        // The receptor writes 0 to the death locus every tick, after the organ has failed.
+        let genome = parse_genome("organ vital toxin 9 repair 8
+    initial 9 255
+end
+receptor digital chem 1 > 0 -> locus death nominal 0 gain 255").unwrap().0;
+        for &mode in [TickMode::Sequential, TickMode::DoubleBuffered].iter() {
+            let mut creature = Creature::with_sex(Sex::Female);
+            genome.init(&mut creature);
+            for _ in 0 .. 3 {
+                genome.tick(&mut creature, mode);
+                assert!(!creature.is_alive());
+                assert_eq!(creature.get_locus(Locus::Death as u8), 255);
+            }
+            assert_eq!(creature.cause_of_death(), Some(DeathCause::OrganFailure(0)));
+            creature.set_locus(Locus::Death as u8, 0);
+            assert!(!creature.is_alive());
+        }
+    }
+
+    #[test]
+    fn sex_linked_organs_keep_their_compartments() {
+        // The female-only organ is poisoned by its own toxin, the organ after it is not.
+        let (mut genome, _) = parse_genome("organ toxin 9 repair 8
+    initial 9 255
+end
+organ toxin 7 repair 8
+end").unwrap();
+        let genes = genome.genes().to_vec();
+        genome = Genome::from_genes(vec![
+            Gene::SexLinked(Sex::Female, Box::new(genes[0].clone())), genes[1].clone()
+        ]);
+        let mut female = Creature::with_sex(Sex::Female);
+        let mut male = Creature::with_sex(Sex::Male);
+        for creature in vec![&mut female, &mut male] {
+            genome.init(creature);
+            genome.tick(creature, TickMode::Sequential);
+        }
+        assert_eq!((female.organs(), female.life_force(0), female.life_force(1)), (2, 0, 255));
+        assert_eq!((male.organs(), male.life_force(0)), (1, 255));
+    }
+}
@@ -0,0 +1,128 @@
+use std::cmp::Ordering;
+
+/// A point in objective space. Every objective is minimised.
+pub type Objectives = Vec<f32>;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rank {
+    pub front: usize,
+    pub crowding: f32,
+}
+
+impl Rank {
+    /// NSGA-II's crowded-comparison operator: lower fronts first, then less crowded points.
+    pub fn crowded_cmp(&self, other: &Rank) -> Ordering {
+        match self.front.cmp(&other.front) {
+            Ordering::Equal => {
+                other.crowding.partial_cmp(&self.crowding).unwrap_or(Ordering::Equal)
+            },
+            ord => ord,
+        }
+    }
+}
+
+pub fn dominates(a: &[f32], b: &[f32]) -> bool {
+    a.iter().zip(b.iter()).all(|(x, y)| x <= y) && a.iter().zip(b.iter()).any(|(x, y)| x < y)
+}
+
+pub fn non_dominated_sort(points: &[Objectives]) -> Vec<Vec<usize>> {
+    let mut dominated_count = vec![0; points.len()];
+    let mut dominated_sets = vec![Vec::new(); points.len()];
+    let mut fronts = vec![Vec::new()];
+    for p in 0 .. points.len() {
+        for q in 0 .. points.len() {
+            if dominates(&points[p], &points[q]) {
+                dominated_sets[p].push(q);
+            } else if dominates(&points[q], &points[p]) {
+                dominated_count[p] += 1;
+            }
+        }
+        if dominated_count[p] == 0 {
+            fronts[0].push(p);
+        }
+    }
+    loop {
+        let mut next = Vec::new();
+        for &p in fronts.last().unwrap().iter() {
+            for &q in dominated_sets[p].iter() {
+                dominated_count[q] -= 1;
+                if dominated_count[q] == 0 {
+                    next.push(q);
+                }
+            }
+        }
+        if next.is_empty() { break }
+        fronts.push(next);
+    }
+    fronts.retain(|front| !front.is_empty());
+    fronts
+}
+
+/// Computes the crowding distance of each member of `front`, in the same order as `front`.
+pub fn crowding_distance(points: &[Objectives], front: &[usize]) -> Vec<f32> {
+    let mut distance = vec![0.0; front.len()];
+    let objective_count = front.first().map_or(0, |&p| points[p].len());
+    for m in 0 .. objective_count {
+        let mut order: Vec<usize> = (0 .. front.len()).collect();
+        order.sort_by(|&a, &b| {
+            points[front[a]][m].partial_cmp(&points[front[b]][m]).unwrap_or(Ordering::Equal)
+        });
+        let lo = points[front[order[0]]][m];
+        let hi = points[front[order[order.len() - 1]]][m];
+        distance[order[0]] = ::std::f32::INFINITY;
+        distance[order[order.len() - 1]] = ::std::f32::INFINITY;
+        if hi - lo <= 0.0 { continue }
+        for i in 1 .. order.len().saturating_sub(1) {
+            let gap = points[front[order[i + 1]]][m] - points[front[order[i - 1]]][m];
+            distance[order[i]] += gap / (hi - lo);
+        }
+    }
+    distance
+}
+
+pub fn rank(points: &[Objectives]) -> Vec<Rank> {
+    let mut ranks = vec![Rank { front: 0, crowding: 0.0 }; points.len()];
+    for (n, front) in non_dominated_sort(points).iter().enumerate() {
+        for (&p, d) in front.iter().zip(crowding_distance(points, front)) {
+            ranks[p] = Rank { front: n, crowding: d };
+        }
+    }
+    ranks
+}
+
+/// Picks the `count` best points by non-dominated sorting, breaking ties within the last admitted
+/// front by crowding distance.
+pub fn select(points: &[Objectives], count: usize) -> Vec<usize> {
+    let ranks = rank(points);
+    let mut order: Vec<usize> = (0 .. points.len()).collect();
+    order.sort_by(|&a, &b| ranks[a].crowded_cmp(&ranks[b]));
+    order.truncate(count);
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points() -> Vec<Objectives> {
+        vec![vec![1.0, 5.0], vec![2.0, 3.0], vec![4.0, 1.0], vec![3.0, 4.0], vec![5.0, 5.0],
+             vec![2.0, 3.0]]
+    }
+
+    #[test]
+    fn points_are_sorted_into_fronts() {
+        // Equal points do not dominate each other, so both copies of (2, 3) share a front.
+        assert_eq!(non_dominated_sort(&points()), vec![vec![0, 1, 2, 5], vec![3], vec![4]]);
+    }
+
+    #[test]
+    fn boundary_points_are_least_crowded() {
+        let points = points();
+        let distance = crowding_distance(&points, &[0, 1, 2]);
+        assert_eq!(distance[0], ::std::f32::INFINITY);
+        assert_eq!(distance[2], ::std::f32::INFINITY);
+        // The middle point's neighbours are 3/3 apart in the first objective and 4/4 in the second.
+        assert_eq!(distance[1], 2.0);
+        assert_eq!(select(&points, 2), vec![0, 2]);
+    }
+}
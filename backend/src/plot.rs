@@ -0,0 +1,107 @@
+use creature::Age;
+use dsl::Names;
+use recorder::{Channel, Recorder};
+
+const WIDTH: f32 = 800.0;
+const PANEL_HEIGHT: f32 = 240.0;
+const LEFT: f32 = 40.0;
+const RIGHT: f32 = 170.0;
+const TOP: f32 = 30.0;
+const BOTTOM: f32 = 30.0;
+const COLOURS: [&'static str; 10] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd",
+    "#8c564b", "#e377c2", "#7f7f7f", "#bcbd22", "#17becf",
+];
+
+/// Renders a recording as an SVG document with one line chart for chemicals and another for
+/// loci. Channels that stayed at zero throughout are left out, and each of `stages` (the tick a
+/// creature reached an age, as seen by `Creature::age`) is marked with a vertical line.
+pub fn plot_svg(recorder: &Recorder, names: &Names, stages: &[(u64, Age)]) -> String {
+    let active: Vec<Channel> = recorder.channels().iter().cloned().filter(|&c| {
+        recorder.column(c).map_or(false, |column| column.iter().any(|&x| x != 0))
+    }).collect();
+    let (chemicals, loci): (Vec<Channel>, Vec<Channel>) = active.into_iter().partition(|c| {
+        match *c {
+            Channel::Chemical(_) => true,
+            Channel::Locus(_) => false,
+        }
+    });
+    let panels: Vec<(&str, Vec<Channel>)> = vec![("Chemicals", chemicals), ("Loci", loci)]
+        .into_iter().filter(|&(_, ref channels)| !channels.is_empty()).collect();
+
+    let height = PANEL_HEIGHT * panels.len().max(1) as f32;
+    let mut out = format!(concat!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" ",
+        "font-family=\"sans-serif\" font-size=\"11\">\n",
+        "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n"
+    ), WIDTH, height);
+    if panels.is_empty() {
+        out.push_str("<text x=\"20\" y=\"30\">Nothing was recorded.</text>\n");
+    }
+    let last = if recorder.is_empty() { 0 } else { recorder.tick(recorder.len() - 1) };
+    for (n, &(title, ref channels)) in panels.iter().enumerate() {
+        out.push_str(&panel(recorder, names, stages, title, channels, last,
+                            n as f32 * PANEL_HEIGHT));
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+fn panel(recorder: &Recorder, names: &Names, stages: &[(u64, Age)], title: &str,
+         channels: &[Channel], last: u64, offset: f32) -> String {
+    let (plot_width, plot_height) = (WIDTH - LEFT - RIGHT, PANEL_HEIGHT - TOP - BOTTOM);
+    let x = |tick: u64| LEFT + tick as f32 / last.max(1) as f32 * plot_width;
+    let y = |value: u8| offset + TOP + (1.0 - value as f32 / 255.0) * plot_height;
+    let mut out = format!("<text x=\"{}\" y=\"{}\" font-size=\"13\">{}</text>\n", LEFT,
+                          offset + TOP - 10.0, title);
+
+    for &value in [0, 64, 128, 192, 255].iter() {
+        out.push_str(&format!(concat!(
+            "<line x1=\"{0}\" y1=\"{1}\" x2=\"{2}\" y2=\"{1}\" stroke=\"#ddd\"/>\n",
+            "<text x=\"{3}\" y=\"{4}\" text-anchor=\"end\">{5}</text>\n"
+        ), LEFT, y(value), LEFT + plot_width, LEFT - 4.0, y(value) + 4.0, value));
+    }
+    for n in 0 .. 6 {
+        let tick = last * n / 5;
+        out.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n",
+            x(tick), offset + TOP + plot_height + 14.0, tick
+        ));
+    }
+    for &(tick, ref age) in stages.iter().filter(|&&(tick, _)| tick <= last) {
+        out.push_str(&format!(concat!(
+            "<line x1=\"{0}\" y1=\"{1}\" x2=\"{0}\" y2=\"{2}\" stroke=\"#888\" ",
+            "stroke-dasharray=\"4,3\"/>\n",
+            "<text x=\"{3}\" y=\"{4}\" fill=\"#888\">{5:?}</text>\n"
+        ), x(tick), offset + TOP, offset + TOP + plot_height, x(tick) + 3.0, offset + TOP + 10.0,
+           age));
+    }
+
+    for (n, &channel) in channels.iter().enumerate() {
+        let colour = COLOURS[n % COLOURS.len()];
+        let column = recorder.column(channel).unwrap_or(&[]);
+        let points: Vec<String> = column.iter().enumerate().map(|(i, &value)| {
+            format!("{:.1},{:.1}", x(recorder.tick(i)), y(value))
+        }).collect();
+        out.push_str(&format!(
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\" points=\"{}\"/>\n",
+            colour, points.join(" ")
+        ));
+        let label = match channel {
+            Channel::Chemical(id) => names.chemical(id),
+            Channel::Locus(id) => names.locus(id),
+        };
+        let row = offset + TOP + 12.0 * n as f32;
+        out.push_str(&format!(concat!(
+            "<line x1=\"{0}\" y1=\"{1}\" x2=\"{2}\" y2=\"{1}\" stroke=\"{3}\" ",
+            "stroke-width=\"3\"/>\n",
+            "<text x=\"{4}\" y=\"{5}\">{6}</text>\n"
+        ), WIDTH - RIGHT + 10.0, row, WIDTH - RIGHT + 25.0, colour, WIDTH - RIGHT + 30.0,
+           row + 4.0, escape(&label)));
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
@@ -0,0 +1,103 @@
+use creature::{Creature, Sex};
+use events::Event;
+use genome::{Genome, TickMode};
+
+pub type CreatureId = u64;
+
+/// A creature in a population, along with the genome it grows from.
+pub struct Member {
+    pub id: CreatureId,
+    pub genome: Genome,
+    pub creature: Creature,
+}
+
+/// Many creatures stepped together. Every creature born into a population gets an id that is
+/// never reused, even after it is removed.
+pub struct Population {
+    members: Vec<Member>,
+    next_id: CreatureId,
+    mode: TickMode,
+}
+
+impl Population {
+    pub fn new() -> Population {
+        Population::with_mode(TickMode::Sequential)
+    }
+
+    pub fn with_mode(mode: TickMode) -> Population {
+        Population { members: Vec::new(), next_id: 0, mode: mode }
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    pub fn members(&self) -> &[Member] {
+        &self.members
+    }
+
+    pub fn members_mut(&mut self) -> &mut [Member] {
+        &mut self.members
+    }
+
+    pub fn get(&self, id: CreatureId) -> Option<&Member> {
+        self.members.iter().find(|m| m.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: CreatureId) -> Option<&mut Member> {
+        self.members.iter_mut().find(|m| m.id == id)
+    }
+
+    /// Grows a new creature of a random sex from `genome` and adds it, returning its id.
+    pub fn birth(&mut self, genome: Genome) -> CreatureId {
+        let mut creature = Creature::new();
+        genome.init(&mut creature);
+        self.add(genome, creature)
+    }
+
+    pub fn birth_as(&mut self, genome: Genome, sex: Sex) -> CreatureId {
+        let mut creature = Creature::with_sex(sex);
+        genome.init(&mut creature);
+        self.add(genome, creature)
+    }
+
+    /// Adds a creature that is already alive, such as one moved from another population.
+    pub fn add(&mut self, genome: Genome, creature: Creature) -> CreatureId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.members.push(Member { id: id, genome: genome, creature: creature });
+        id
+    }
+
+    pub fn remove(&mut self, id: CreatureId) -> Option<Member> {
+        self.members.iter().position(|m| m.id == id).map(|n| self.members.remove(n))
+    }
+
+    /// Removes every creature for which `keep` returns false, returning them in order.
+    pub fn remove_unless<F: FnMut(&Member) -> bool>(&mut self, mut keep: F) -> Vec<Member> {
+        let (kept, removed) = self.members.drain(..).partition(|m| keep(m));
+        self.members = kept;
+        removed
+    }
+
+    pub fn remove_dead(&mut self) -> Vec<Member> {
+        self.remove_unless(|m| m.creature.is_alive())
+    }
+
+    /// Steps every living creature once, in the order they were added, and drains their events.
+    pub fn step(&mut self) -> Vec<(CreatureId, u64, Event)> {
+        let mut events = Vec::new();
+        for member in self.members.iter_mut().filter(|m| m.creature.is_alive()) {
+            member.genome.tick(&mut member.creature, self.mode);
+            let id = member.id;
+            events.extend(member.creature.drain_events().into_iter().map(|(tick, event)| {
+                (id, tick, event)
+            }));
+        }
+        events
+    }
+}
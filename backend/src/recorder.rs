@@ -0,0 +1,248 @@
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use chem::Id;
+use creature::{Creature, LocusId};
+use dsl::Names;
+
+const MAGIC: &'static [u8; 4] = b"CREC";
+
+/// Something that watches a creature tick by tick, see `Genome::step_with`.
+pub trait Observer {
+    fn observe(&mut self, creature: &Creature);
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Channel {
+    Chemical(Id),
+    Locus(LocusId),
+}
+
+impl Channel {
+    fn read(&self, creature: &Creature) -> u8 {
+        match *self {
+            Channel::Chemical(id) => creature.chemo_body().concnt(id),
+            Channel::Locus(id) => creature.get_locus(id),
+        }
+    }
+
+    /// The column heading for this channel, e.g. `chem:glucose` or `locus:hunger`.
+    pub fn label(&self, names: &Names) -> String {
+        match *self {
+            Channel::Chemical(id) => format!("chem:{}", names.chemical(id)),
+            Channel::Locus(id) => format!("locus:{}", names.locus(id)),
+        }
+    }
+}
+
+/// Records chemical concentrations and locus values over time, one column per channel.
+/// Concentrations are recorded in whole units, so with the `float-concentration` feature any
+/// fraction of a unit is dropped.
+#[derive(Clone, Debug)]
+pub struct Recorder {
+    channels: Vec<Channel>,
+    interval: u32,
+    ticks: u64,
+    columns: Vec<Vec<u8>>,
+}
+
+impl Recorder {
+    /// A recorder for every chemical and every locus, sampling once every `interval` ticks.
+    pub fn new(interval: u32) -> Recorder {
+        let chemicals = (0 .. 256).map(|id| Channel::Chemical(id as Id));
+        let loci = (0 .. 256).map(|id| Channel::Locus(id as LocusId));
+        Recorder::with_channels(chemicals.chain(loci).collect(), interval)
+    }
+
+    /// A recorder for only the given channels, sampling once every `interval` ticks.
+    pub fn with_channels(channels: Vec<Channel>, interval: u32) -> Recorder {
+        assert!(interval > 0, "sampling interval must be positive");
+        Recorder {
+            columns: vec![Vec::new(); channels.len()], channels: channels, interval: interval,
+            ticks: 0
+        }
+    }
+
+    pub fn channels(&self) -> &[Channel] {
+        &self.channels
+    }
+
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    /// The number of samples taken.
+    pub fn len(&self) -> usize {
+        self.columns.first().map_or(0, |c| c.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The tick at which the `n`th sample was taken, counting the first observation as tick 0.
+    pub fn tick(&self, n: usize) -> u64 {
+        n as u64 * self.interval as u64
+    }
+
+    /// Every sample taken for a channel, or `None` if it is not being recorded.
+    pub fn column(&self, channel: Channel) -> Option<&[u8]> {
+        self.channels.iter().position(|&c| c == channel).map(|n| &self.columns[n][..])
+    }
+
+    pub fn write_csv<W: Write>(&self, out: &mut W, names: &Names) -> Result<()> {
+        try!(write!(out, "tick"));
+        for channel in self.channels.iter() {
+            try!(write!(out, ",{}", channel.label(names)));
+        }
+        try!(writeln!(out, ""));
+        for n in 0 .. self.len() {
+            try!(write!(out, "{}", self.tick(n)));
+            for column in self.columns.iter() {
+                try!(write!(out, ",{}", column[n]));
+            }
+            try!(writeln!(out, ""));
+        }
+        Ok(())
+    }
+
+    /// Writes the recording as a header followed by each column's raw bytes. Ticks are not
+    /// stored, since they follow from the sampling interval.
+    pub fn write_binary<W: Write>(&self, out: &mut W) -> Result<()> {
+        try!(out.write_all(MAGIC));
+        try!(out.write_all(&le_bytes(self.interval as u64, 4)));
+        try!(out.write_all(&le_bytes(self.channels.len() as u64, 2)));
+        try!(out.write_all(&le_bytes(self.len() as u64, 8)));
+        for channel in self.channels.iter() {
+            try!(out.write_all(&match *channel {
+                Channel::Chemical(id) => [0, id],
+                Channel::Locus(id) => [1, id],
+            }));
+        }
+        for column in self.columns.iter() {
+            try!(out.write_all(column));
+        }
+        out.flush()
+    }
+
+    pub fn read_binary<R: Read>(input: &mut R) -> Result<Recorder> {
+        let invalid = || Error::new(ErrorKind::InvalidData, "Failed to decode recording.");
+        let mut magic = [0; 4];
+        try!(input.read_exact(&mut magic));
+        if &magic != MAGIC { return Err(invalid()) }
+        let interval = try!(read_le(input, 4)) as u32;
+        let channels = try!(read_le(input, 2)) as usize;
+        let len = try!(read_le(input, 8)) as usize;
+        if interval == 0 { return Err(invalid()) }
+        let mut recorder = Recorder::with_channels(Vec::with_capacity(channels), interval);
+        for _ in 0 .. channels {
+            let mut channel = [0; 2];
+            try!(input.read_exact(&mut channel));
+            recorder.channels.push(match channel {
+                [0, id] => Channel::Chemical(id),
+                [1, id] => Channel::Locus(id),
+                _ => return Err(invalid()),
+            });
+        }
+        for _ in 0 .. channels {
+            // Grow each column as its bytes arrive rather than trusting the header's length, so a
+            // corrupt file cannot make us allocate more than it holds.
+            let mut column = Vec::new();
+            try!(input.by_ref().take(len as u64).read_to_end(&mut column));
+            if column.len() != len { return Err(invalid()) }
+            recorder.columns.push(column);
+        }
+        recorder.ticks = len as u64 * interval as u64;
+        Ok(recorder)
+    }
+}
+
+/// Recordings are equal if they sampled the same channels at the same times, regardless of how
+/// many ticks have passed since their last samples.
+impl PartialEq for Recorder {
+    fn eq(&self, other: &Recorder) -> bool {
+        self.channels == other.channels && self.interval == other.interval &&
+            self.columns == other.columns
+    }
+}
+
+impl Observer for Recorder {
+    fn observe(&mut self, creature: &Creature) {
+        if self.ticks % self.interval as u64 == 0 {
+            for (channel, column) in self.channels.iter().zip(self.columns.iter_mut()) {
+                column.push(channel.read(creature));
+            }
+        }
+        self.ticks += 1;
+    }
+}
+
+fn le_bytes(value: u64, width: usize) -> Vec<u8> {
+    (0 .. width).map(|n| (value >> (8 * n)) as u8).collect()
+}
+
+fn read_le<R: Read>(input: &mut R, width: usize) -> Result<u64> {
+    let mut bytes = vec![0; width];
+    try!(input.read_exact(&mut bytes));
+    Ok(bytes.iter().rev().fold(0, |value, &b| value << 8 | b as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use creature::Creature;
+    use dsl::{parse_genome, Names};
+    use super::*;
+
+    fn recording() -> Recorder {
+        let (genome, _) = parse_genome("initial 1 10\nemitter digital locus 40 >= 0 -> +1 chem 1")
+            .unwrap();
+        let mut creature = Creature::new();
+        genome.init(&mut creature);
+        let mut recorder = Recorder::with_channels(vec![Channel::Chemical(1),
+                                                        Channel::Locus(40)], 2);
+        for _ in 0 .. 5 {
+            genome.step_with(&mut creature, &mut recorder);
+        }
+        recorder
+    }
+
+    fn binary(recorder: &Recorder) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        recorder.write_binary(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn samples_every_interval() {
+        let recorder = recording();
+        assert_eq!(recorder.len(), 3);
+        assert_eq!(recorder.column(Channel::Chemical(1)), Some(&[11, 13, 15][..]));
+        assert_eq!(recorder.column(Channel::Chemical(2)), None);
+        let mut csv = Vec::new();
+        recorder.write_csv(&mut csv, &Names::new()).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(),
+                   "tick,chem:1,locus:40\n0,11,0\n2,13,0\n4,15,0\n");
+    }
+
+    #[test]
+    fn binary_round_trips() {
+        let recorder = recording();
+        let read = Recorder::read_binary(&mut Cursor::new(binary(&recorder))).unwrap();
+        assert!(read == recorder);
+    }
+
+    #[test]
+    fn corrupt_recordings_are_rejected() {
+        let bytes = binary(&recording());
+        let read = |bytes: &[u8]| Recorder::read_binary(&mut Cursor::new(bytes.to_vec()));
+        let mut magic = bytes.clone();
+        magic[0] = b'X';
+        assert!(read(&magic).is_err());
+        assert!(read(&bytes[.. bytes.len() - 1]).is_err());
+        // A length far beyond what the file holds fails rather than allocating it.
+        let mut huge = bytes.clone();
+        for byte in huge[10 .. 18].iter_mut() {
+            *byte = 0xff;
+        }
+        assert!(read(&huge).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use genome::Genome;
+use pareto::Objectives;
+
+pub struct Species {
+    pub representative: Genome,
+    pub members: Vec<usize>,
+}
+
+/// Clusters genomes into species by genetic distance. Representatives carry over between calls to
+/// `speciate` so that species stay stable across generations.
+pub struct Speciation {
+    pub threshold: f32,
+    species: Vec<Species>,
+}
+
+impl Speciation {
+    pub fn new(threshold: f32) -> Speciation {
+        Speciation { threshold: threshold, species: Vec::new() }
+    }
+
+    pub fn species(&self) -> &[Species] {
+        &self.species
+    }
+
+    pub fn species_of(&self, member: usize) -> Option<usize> {
+        self.species.iter().position(|s| s.members.contains(&member))
+    }
+
+    pub fn speciate<'a, I>(&mut self, genomes: I) where I: IntoIterator<Item = &'a Genome> {
+        for species in self.species.iter_mut() {
+            species.members.clear();
+        }
+        let genomes: Vec<&Genome> = genomes.into_iter().collect();
+        for (n, genome) in genomes.iter().enumerate() {
+            let threshold = self.threshold;
+            match self.species.iter_mut().find(|s| s.representative.distance(genome) < threshold) {
+                Some(species) => species.members.push(n),
+                None => self.species.push(Species {
+                    representative: (*genome).clone(), members: vec![n]
+                }),
+            }
+        }
+        self.species.retain(|s| !s.members.is_empty());
+        for species in self.species.iter_mut() {
+            species.representative = genomes[species.members[0]].clone();
+        }
+    }
+
+    /// Applies fitness sharing to minimised, non-negative objectives by scaling each member's
+    /// objectives with the size of its species. Since lower is better, multiplying makes crowded
+    /// niches look worse, though an objective already at 0 stays there.
+    pub fn share(&self, objectives: &[Objectives]) -> Vec<Objectives> {
+        let mut shared = objectives.to_vec();
+        for species in self.species.iter() {
+            for &n in species.members.iter() {
+                for value in shared[n].iter_mut() {
+                    *value *= species.members.len() as f32;
+                }
+            }
+        }
+        shared
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use genome::{Gene, Genome};
+    use life::LifeClock;
+    use pareto::non_dominated_sort;
+    use super::*;
+
+    #[test]
+    fn sharing_favours_the_smaller_species() {
+        let crowded = Genome::new();
+        let rare = Genome::from_genes(vec![Gene::Clock(LifeClock::default())]);
+        let mut speciation = Speciation::new(0.3);
+        speciation.speciate(vec![&crowded, &crowded, &crowded, &rare]);
+        assert_eq!(speciation.species().len(), 2);
+        assert_eq!(speciation.species_of(3), Some(1));
+        // The crowded species is better on its own merits, but not once shared three ways.
+        let points = vec![vec![2.0, 2.0], vec![2.0, 2.0], vec![2.0, 2.0], vec![3.0, 3.0]];
+        assert_eq!(non_dominated_sort(&points), vec![vec![0, 1, 2], vec![3]]);
+        let shared = speciation.share(&points);
+        assert_eq!(shared[0], vec![6.0, 6.0]);
+        assert_eq!(shared[3], vec![3.0, 3.0]);
+        assert_eq!(non_dominated_sort(&shared), vec![vec![3], vec![0, 1, 2]]);
+    }
+}
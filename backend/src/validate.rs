@@ -0,0 +1,271 @@
+use std::collections::HashSet;
+use std::fmt;
+use chem::{Id, IoType};
+use creature::{Locus, LocusId};
+use genome::{Gene, Genome};
+use life::STAGE_LOCI;
+use organ::Direction;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Severity {
+    /// The gene is broken and would misbehave if expressed.
+    Error,
+    /// The gene is well-formed but can never have any effect in this genome.
+    Warning,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Reason {
+    /// An emitter reads a locus that no gene writes, and never emits with the locus left at 0.
+    UnwrittenLocus(LocusId),
+    /// A receptor or reaction needs a chemical that no gene produces.
+    UnproducedChemical(Id),
+    /// An organ contains another organ, which would never be expressed.
+    NestedOrgan,
+    /// A life clock comes after another one, which overrides it.
+    ExtraClock,
+    /// A sex-linked gene wraps an organ, a life clock or another sex-linked gene, none of which
+    /// can be linked to a sex.
+    UnlinkableGene,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub gene: usize,
+    pub reason: Reason,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{} in gene {}: ", match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }, self.gene));
+        match self.reason {
+            Reason::UnwrittenLocus(id) => write!(f, "locus {} is never written", id),
+            Reason::UnproducedChemical(id) => write!(f, "chemical {} is never produced", id),
+            Reason::NestedOrgan => write!(f, "organs cannot contain organs"),
+            Reason::ExtraClock => write!(f, "only the first life clock is used"),
+            Reason::UnlinkableGene => write!(f, "only simple genes can be sex-linked"),
+        }
+    }
+}
+
+/// What `Genome::mutate_with` does with a mutation that leaves a broken or useless gene behind.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Validation {
+    /// Keep the mutation as is.
+    Allow,
+    /// Discard a mutation that leaves the genome with more diagnostics, errors or warnings, than
+    /// it had, and try another.
+    Reject,
+    /// Keep the mutation and fix whatever can be fixed, then drop the mutated gene if it still
+    /// has a diagnostic.
+    Repair,
+}
+
+impl Genome {
+    /// Checks every gene, including those inside organs, whose diagnostics are reported against
+    /// the organ. Loci are shared by the whole creature, while an organ's genes only see the
+    /// chemicals made in the organ or imported into it.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut written: HashSet<LocusId> = STAGE_LOCI.iter().cloned().chain(Some(Locus::Sex))
+            .map(|l| l as LocusId).collect();
+        for gene in self.genes().iter() {
+            match *gene.unlinked() {
+                Gene::Receptor(ref r) => { written.insert(r.locus); },
+                Gene::Organ(ref o) => for gene in o.genes.iter() {
+                    if let Gene::Receptor(ref r) = *gene.unlinked() {
+                        written.insert(r.locus);
+                    }
+                },
+                _ => (),
+            }
+        }
+        let produced = produced_in(self.genes());
+        let mut diagnostics = Vec::new();
+        {
+            let mut report = |severity, gene, reason| diagnostics.push(Diagnostic {
+                severity: severity, gene: gene, reason: reason
+            });
+            let mut clocks = 0;
+            for (n, gene) in self.genes().iter().enumerate() {
+                if let Gene::SexLinked(_, ref linked) = *gene {
+                    if !linkable(linked) {
+                        report(Severity::Error, n, Reason::UnlinkableGene);
+                    }
+                }
+                match *gene.unlinked() {
+                    Gene::Organ(ref o) => {
+                        let mut inside = produced_in(&o.genes);
+                        inside.extend(o.transport.iter().filter(|t| {
+                            t.direction == Direction::Import
+                        }).map(|t| t.chemical));
+                        for gene in o.genes.iter() {
+                            if is_organ(gene) {
+                                report(Severity::Error, n, Reason::NestedOrgan);
+                            }
+                            for reason in unused(gene, &inside, &written) {
+                                report(Severity::Warning, n, reason);
+                            }
+                        }
+                    },
+                    Gene::Clock(..) => {
+                        if clocks > 0 { report(Severity::Warning, n, Reason::ExtraClock) }
+                        clocks += 1;
+                    },
+                    _ => for reason in unused(gene, &produced, &written) {
+                        report(Severity::Warning, n, reason);
+                    },
+                }
+            }
+        }
+        diagnostics
+    }
+
+    /// Fixes every error that has an obvious fix, returning how many genes were changed. Organs
+    /// nested in organs, which are never expressed, are dropped, and genes that cannot be
+    /// sex-linked lose their link.
+    pub fn repair(&mut self) -> usize {
+        let mut fixed = 0;
+        for n in 0 .. self.genes().len() {
+            let gene = match self.genes()[n] {
+                Gene::Organ(ref o) if o.genes.iter().any(is_organ) => {
+                    let mut o = o.clone();
+                    o.genes.retain(|g| !is_organ(g));
+                    Gene::Organ(o)
+                },
+                Gene::SexLinked(_, ref g) if !linkable(g) => g.unlinked().clone(),
+                _ => continue,
+            };
+            self.replace_gene(n, gene);
+            fixed += 1;
+        }
+        fixed
+    }
+}
+
+/// The chemicals that genes make in their compartment, or that organs among them export to it.
+fn produced_in(genes: &[Gene]) -> HashSet<Id> {
+    let mut produced = HashSet::new();
+    for gene in genes.iter() {
+        match *gene.unlinked() {
+            Gene::InitialState(ref c) => { produced.insert(c.id()); },
+            Gene::Emitter(ref e) => { produced.insert(e.chemical); },
+            Gene::Reaction(ref r) => {
+                // A catalyst comes out of a reaction, but only if it was there to begin with.
+                let reactants: Vec<Id> = r.kind.reactants().iter().map(|c| c.id).collect();
+                produced.extend(r.kind.products().iter().map(|c| c.id).filter(|id| {
+                    !reactants.contains(id)
+                }));
+            },
+            Gene::Organ(ref o) => {
+                produced.extend(o.transport.iter().filter(|t| {
+                    t.direction == Direction::Export
+                }).map(|t| t.chemical));
+            },
+            Gene::Receptor(..) | Gene::Brain(..) | Gene::Clock(..) | Gene::SexLinked(..) => (),
+        }
+    }
+    produced
+}
+
+/// Why a gene can never have any effect, given what its compartment produces and what loci are
+/// written.
+fn unused(gene: &Gene, produced: &HashSet<Id>, written: &HashSet<LocusId>) -> Vec<Reason> {
+    match *gene.unlinked() {
+        // An emitter on a locus nobody writes sees 0, or 255 if inverted, and may still emit
+        // all the time.
+        Gene::Emitter(ref e) if !written.contains(&e.locus) => {
+            let signal = if e.invert { 255 } else { 0 };
+            let emits = e.gain > 0 && match e.kind {
+                IoType::Digital => signal >= e.threshold,
+                IoType::Analogue => signal != e.threshold,
+            };
+            if emits { Vec::new() } else { vec![Reason::UnwrittenLocus(e.locus)] }
+        },
+        Gene::Reaction(ref r) => r.kind.reactants().into_iter().filter(|c| {
+            !produced.contains(&c.id)
+        }).map(|c| Reason::UnproducedChemical(c.id)).collect(),
+        Gene::Receptor(ref r) if !produced.contains(&r.chemical) => {
+            vec![Reason::UnproducedChemical(r.chemical)]
+        },
+        _ => Vec::new(),
+    }
+}
+
+fn is_organ(gene: &Gene) -> bool {
+    match *gene.unlinked() {
+        Gene::Organ(..) => true,
+        _ => false,
+    }
+}
+
+fn linkable(gene: &Gene) -> bool {
+    match *gene {
+        Gene::Organ(..) | Gene::Clock(..) | Gene::SexLinked(..) => false,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dsl::parse_genome;
+    use organ::Organ;
+    use super::*;
+
+    fn validate(text: &str) -> Vec<Diagnostic> {
+        parse_genome(text).unwrap().0.validate()
+    }
+
+    fn warning(gene: usize, reason: Reason) -> Diagnostic {
+        Diagnostic { severity: Severity::Warning, gene: gene, reason: reason }
+    }
+
+    #[test]
+    fn genes_that_can_never_act_are_warned_about() {
+        assert_eq!(validate("emitter digital locus 40 >= 1 -> +1 chem 1
+emitter digital locus 41 >= 1 -> +1 chem 1 invert
+receptor digital chem 2 > 0 -> locus hunger nominal 0 gain 255
+reaction decay 1*1 -> nothing"), vec![
+            warning(0, Reason::UnwrittenLocus(40)),
+            warning(2, Reason::UnproducedChemical(2)),
+        ]);
+    }
+
+    #[test]
+    fn organs_are_checked_inside() {
+        // The receptor inside the organ writes locus 40 for the emitter outside it.
+        assert!(validate("initial 1 10
+emitter digital locus 40 >= 1 -> +1 chem 2
+organ toxin 9 repair 9 import 1 5
+    receptor digital chem 1 > 0 -> locus 40 nominal 0 gain 255
+end").is_empty());
+        // Chemicals in the bloodstream are not in the organ unless imported.
+        assert_eq!(validate("initial 1 10
+organ toxin 9 repair 9
+    receptor digital chem 1 > 0 -> locus 40 nominal 0 gain 255
+end"), vec![warning(1, Reason::UnproducedChemical(1))]);
+    }
+
+    #[test]
+    fn repair_unlinks_and_unnests() {
+        let (mut genome, _) = parse_genome("female clock 1 1 1 1 1 1").unwrap();
+        let nested = Organ::new(Vec::new(), false, 9, 9, Vec::new());
+        let genes = vec![genome.genes()[0].unlinked().clone(), Gene::Organ(nested)];
+        genome.insert_gene(Gene::Organ(Organ::new(genes, false, 9, 9, Vec::new())));
+        assert_eq!(genome.validate().iter().filter(|d| d.severity == Severity::Error).count(), 2);
+        assert_eq!(genome.repair(), 2);
+        assert!(genome.validate().iter().all(|d| d.severity != Severity::Error));
+    }
+
+    #[test]
+    fn rejecting_never_adds_diagnostics() {
+        let mut genome = Genome::new();
+        for _ in 0 .. 200 {
+            genome = genome.mutate_with(Validation::Reject);
+            assert!(genome.validate().is_empty());
+        }
+    }
+}
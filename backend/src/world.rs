@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use breeding::Breeding;
+use chem::{Concentration, Id};
+use creature::{Age, Creature, Drive, Locus, LocusValue};
+use death::DeathCause;
+use events::Event;
+use inject::{Dose, Injector};
+use population::{CreatureId, Member, Population};
+
+/// The food a world grows and how its creatures live off it. The world holds up to `capacity`
+/// units of food, growing back by `regrowth` a tick. Every tick each creature burns `metabolism`
+/// units of the `food` chemical from its bloodstream, and one that cannot for `starvation` ticks
+/// in a row starves to death. Creatures whose hunger is at least `appetite` eat up to a `ration`,
+/// hungriest first, for as long as the food lasts, and digest it over the following ticks.
+/// Creatures die of old age on reaching `lifespan`, so that the old make way for the young.
+#[derive(Copy, Clone, Debug)]
+pub struct Environment {
+    pub food: Id,
+    pub capacity: u32,
+    pub regrowth: u32,
+    pub ration: Concentration,
+    pub metabolism: Concentration,
+    pub appetite: LocusValue,
+    pub starvation: u32,
+    pub lifespan: Age,
+}
+
+impl Default for Environment {
+    fn default() -> Environment {
+        Environment {
+            food: 1, capacity: 5000, regrowth: 100, ration: 20, metabolism: 1, appetite: 128,
+            starvation: 50, lifespan: Age::Senile
+        }
+    }
+}
+
+/// How a world's population has fared since the last census.
+#[derive(Clone, Debug)]
+pub struct Census {
+    pub tick: u64,
+    pub alive: usize,
+    pub born: usize,
+    pub starved: usize,
+    /// Deaths of anything other than starvation.
+    pub died: usize,
+    /// Food left in the world.
+    pub food: u32,
+    pub mean_generation: f32,
+    pub max_generation: u32,
+    pub mean_genes: f32,
+}
+
+/// A population left to eat, starve and breed in an environment, with no fitness function but
+/// staying alive long enough to have children.
+pub struct World {
+    population: Population,
+    environment: Environment,
+    breeding: Breeding,
+    food: u32,
+    tick: u64,
+    /// How many ticks in a row each creature has gone without food.
+    hungry: HashMap<CreatureId, u32>,
+    /// What each creature has eaten, and is still digesting.
+    guts: HashMap<CreatureId, Injector>,
+    born: usize,
+    starved: usize,
+    died: usize,
+}
+
+impl World {
+    /// A world full of food around `population`.
+    pub fn new(population: Population, environment: Environment, breeding: Breeding) -> World {
+        World {
+            population: population, environment: environment, breeding: breeding,
+            food: environment.capacity, tick: 0, hungry: HashMap::new(), guts: HashMap::new(),
+            born: 0, starved: 0, died: 0
+        }
+    }
+
+    pub fn population(&self) -> &Population {
+        &self.population
+    }
+
+    pub fn population_mut(&mut self) -> &mut Population {
+        &mut self.population
+    }
+
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    pub fn food(&self) -> u32 {
+        self.food
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Runs the world for a tick: food grows back and is eaten, creatures are stepped, burn food
+    /// and breed, and the dead are removed. Returns every event that happened in the tick, births
+    /// and the last events of those that died included.
+    pub fn step(&mut self) -> Vec<(CreatureId, u64, Event)> {
+        let env = self.environment;
+        self.tick += 1;
+        self.food = (self.food + env.regrowth).min(env.capacity);
+        self.feed();
+        for member in self.population.members_mut().iter_mut() {
+            if !member.creature.is_alive() { continue }
+            let gut = self.guts.entry(member.id).or_insert_with(Injector::new);
+            gut.step(&mut member.creature);
+            // A world's meals are never replayed, so there is no need to remember them.
+            gut.drain_log();
+        }
+        let mut events = self.population.step();
+        for member in self.population.members_mut().iter_mut() {
+            let creature = &mut member.creature;
+            if !creature.is_alive() { continue }
+            if creature.age() >= env.lifespan {
+                kill(creature, DeathCause::OldAge);
+                continue
+            }
+            let hungry = self.hungry.entry(member.id).or_insert(0);
+            if creature.chemo_body_mut().lose(env.food, env.metabolism) {
+                *hungry = 0;
+                continue
+            }
+            let left = creature.chemo_body().concnt(env.food);
+            creature.chemo_body_mut().lose(env.food, left);
+            *hungry += 1;
+            if *hungry >= env.starvation {
+                kill(creature, DeathCause::Starvation);
+            }
+        }
+        self.born += self.population.breed(&self.breeding).len();
+        // Births and conceptions are reported in the tick they happen, along with deaths.
+        for member in self.population.members_mut().iter_mut() {
+            let id = member.id;
+            events.extend(member.creature.drain_events().into_iter().map(|(tick, event)| {
+                (id, tick, event)
+            }));
+        }
+        for member in self.population.remove_dead() {
+            self.bury(&member);
+        }
+        events
+    }
+
+    /// Shares out food among the creatures hungry enough to eat, hungriest first.
+    fn feed(&mut self) {
+        let env = self.environment;
+        let mut eaters: Vec<(LocusValue, usize)> = self.population.members().iter().enumerate()
+            .filter(|&(_, m)| m.creature.is_alive())
+            .map(|(n, m)| (m.creature.get_drive(Drive::Hunger), n))
+            .filter(|&(hunger, _)| hunger >= env.appetite).collect();
+        eaters.sort_by(|a, b| b.0.cmp(&a.0));
+        let members = self.population.members_mut();
+        for (_, n) in eaters {
+            if self.food < env.ration as u32 { break }
+            let member = &mut members[n];
+            let gut = self.guts.entry(member.id).or_insert_with(Injector::new);
+            // Nobody eats more than they have room for, counting what they are still digesting.
+            let full = member.creature.chemo_body().concnt(env.food) as u32 +
+                gut.in_gut(env.food) as u32;
+            let ration = (env.ration as u32).min(255 - full.min(255));
+            if ration == 0 { continue }
+            self.food -= ration;
+            gut.feed(&mut member.creature, &Dose::new("food").with(env.food, ration as u8));
+        }
+    }
+
+    fn bury(&mut self, member: &Member) {
+        self.hungry.remove(&member.id);
+        self.guts.remove(&member.id);
+        if member.creature.cause_of_death() == Some(DeathCause::Starvation) {
+            self.starved += 1;
+        } else {
+            self.died += 1;
+        }
+    }
+
+    /// Counts the population and everything that has happened to it since the last census.
+    pub fn census(&mut self) -> Census {
+        let members = self.population.members();
+        let count = members.len().max(1) as f32;
+        let census = Census {
+            tick: self.tick,
+            alive: members.len(),
+            born: self.born,
+            starved: self.starved,
+            died: self.died,
+            food: self.food,
+            mean_generation: members.iter().fold(0.0, |acc, m| {
+                acc + m.genome.lineage().generation as f32
+            }) / count,
+            max_generation: members.iter().map(|m| m.genome.lineage().generation).max()
+                                   .unwrap_or(0),
+            mean_genes: members.iter().fold(0.0, |acc, m| {
+                acc + m.genome.genes().len() as f32
+            }) / count,
+        };
+        self.born = 0;
+        self.starved = 0;
+        self.died = 0;
+        census
+    }
+}
+
+/// Kills a creature from outside its genome, which will not report its own death.
+fn kill(creature: &mut Creature, cause: DeathCause) {
+    creature.record_death(cause);
+    creature.set_locus(Locus::Death as u8, 255);
+    creature.emit(Event::Died(cause));
+}
+
+#[cfg(test)]
+mod tests {
+    use dsl::parse_genome;
+    use super::*;
+
+    #[test]
+    fn births_are_reported_in_the_tick_they_happen() {
+        let (genome, _) = parse_genome("initial 1 100").unwrap();
+        let mut population = Population::new();
+        population.birth(genome.clone());
+        population.birth(genome);
+        // Everyone is hungry enough to eat, willing and fertile from birth, and a pregnancy lasts
+        // a single tick.
+        let env = Environment { appetite: 0, .. Environment::default() };
+        let breeding = Breeding {
+            maturity: Age::Baby, sex_drive: 0, fertility: 0, gestation: 255, mutations: 0,
+            opposite_sexes: false, .. Breeding::default()
+        };
+        let mut world = World::new(population, env, breeding);
+        let conceived = world.step().into_iter().any(|(_, _, event)| match event {
+            Event::Conceived { .. } => true,
+            _ => false,
+        });
+        assert!(conceived);
+        let born: Vec<CreatureId> = world.step().into_iter().filter_map(|(_, _, event)| {
+            match event {
+                Event::GaveBirth { child } => Some(child),
+                _ => None,
+            }
+        }).collect();
+        assert_eq!(born.len(), 1);
+        assert!(world.population().get(born[0]).is_some());
+        assert!(world.guts.values().all(|gut| gut.log().injections.is_empty()));
+    }
+}